@@ -15,6 +15,7 @@ pub mod tests {
         
 
         #[test]
+        #[allow(clippy::map_clone)]
         fn new() {
             let mut queue: Queue<String> = Queue::new();
 
@@ -22,13 +23,13 @@ pub mod tests {
                 "first".to_string(),
                 Some(1),
                 LockStatus::Unlocked,
-            ));
-            queue.push(Prio::new("third".to_string(), Some(0), LockStatus::Locked));
+            )).unwrap();
+            queue.push(Prio::new("third".to_string(), Some(0), LockStatus::Locked)).unwrap();
             queue.push(Prio::new(
                 "second".to_string(),
                 Some(2),
                 LockStatus::Unlocked,
-            ));
+            )).unwrap();
 
             assert_eq!(
                 vec!["third", "first", "second"],
@@ -39,7 +40,7 @@ pub mod tests {
                     .collect::<Vec<_>>()
             );
 
-            queue.push(Prio::wolock("fourth".to_string(), Some(0)));
+            queue.push(Prio::wolock("fourth".to_string(), Some(0))).unwrap();
 
             assert_eq!(
                 vec!["third", "fourth", "first", "second"],
@@ -55,10 +56,10 @@ pub mod tests {
         fn test_lockable_queue() {
             let mut queue = Queue::new();
 
-            queue.push(Prio::new("first", Some(2), LockStatus::Unlocked)); // Priority 2
-            queue.push(Prio::new("second", Some(1), LockStatus::Locked)); // Priority 1 (locked)
-            queue.push(Prio::new("third", Some(1), LockStatus::Unlocked)); // Priority 1 (unlocked)
-            queue.push(Prio::new("fourth", Some(0), LockStatus::Locked)); // Highest priority (locked)
+            queue.push(Prio::new("first", Some(2), LockStatus::Unlocked)).unwrap(); // Priority 2
+            queue.push(Prio::new("second", Some(1), LockStatus::Locked)).unwrap(); // Priority 1 (locked)
+            queue.push(Prio::new("third", Some(1), LockStatus::Unlocked)).unwrap(); // Priority 1 (unlocked)
+            queue.push(Prio::new("fourth", Some(0), LockStatus::Locked)).unwrap(); // Highest priority (locked)
 
             let elements = queue.get_elements();
 
@@ -69,21 +70,21 @@ pub mod tests {
 
             assert_eq!(vec!["second", "third", "first"], queue.get_elements());
 
-            queue.push(Prio::new("fifth", Some(0), LockStatus::Locked));
+            queue.push(Prio::new("fifth", Some(0), LockStatus::Locked)).unwrap();
 
             assert_eq!(
                 vec!["fifth", "second", "third", "first"],
                 queue.get_elements()
             );
 
-            queue.push(Prio::new("sixth", Some(0), LockStatus::Locked));
+            queue.push(Prio::new("sixth", Some(0), LockStatus::Locked)).unwrap();
 
             assert_eq!(
                 vec!["fifth", "sixth", "second", "third", "first"],
                 queue.get_elements()
             );
 
-            queue.push(Prio::new("seventh", Some(0), LockStatus::Locked));
+            queue.push(Prio::new("seventh", Some(0), LockStatus::Locked)).unwrap();
 
             assert_eq!(
                 vec!["fifth", "sixth", "seventh", "second", "third", "first"],
@@ -95,10 +96,10 @@ pub mod tests {
         fn test_unlocked_lockable_queue() {
             let mut queue = Queue::new();
 
-            queue.push(Prio::new("first", Some(2), LockStatus::Unlocked)); // Priority 2
-            queue.push(Prio::new("second", Some(1), LockStatus::Unlocked)); // Priority 1 (unlocked)
-            queue.push(Prio::new("third", Some(1), LockStatus::Unlocked)); // Priority 1 (unlocked)
-            queue.push(Prio::new("fourth", Some(0), LockStatus::Unlocked)); // Highest priority (unlocked)
+            queue.push(Prio::new("first", Some(2), LockStatus::Unlocked)).unwrap(); // Priority 2
+            queue.push(Prio::new("second", Some(1), LockStatus::Unlocked)).unwrap(); // Priority 1 (unlocked)
+            queue.push(Prio::new("third", Some(1), LockStatus::Unlocked)).unwrap(); // Priority 1 (unlocked)
+            queue.push(Prio::new("fourth", Some(0), LockStatus::Unlocked)).unwrap(); // Highest priority (unlocked)
 
             assert_eq!(
                 vec!["fourth", "second", "third", "first"],
@@ -106,7 +107,7 @@ pub mod tests {
                     .get_elements()
             );
 
-            queue.push(Prio::new("fifth", Some(0), LockStatus::Unlocked));
+            queue.push(Prio::new("fifth", Some(0), LockStatus::Unlocked)).unwrap();
 
             assert_eq!(
                 vec!["fourth", "fifth", "second", "third", "first"],
@@ -114,7 +115,7 @@ pub mod tests {
                     .get_elements()
             );
 
-            queue.push(Prio::new("sixth", Some(0), LockStatus::Unlocked));
+            queue.push(Prio::new("sixth", Some(0), LockStatus::Unlocked)).unwrap();
 
             assert_eq!(
                 vec!["fourth", "fifth", "sixth", "second", "third", "first"],
@@ -122,7 +123,7 @@ pub mod tests {
                     .get_elements()
             );
 
-            queue.push(Prio::new("seventh", Some(0), LockStatus::Unlocked));
+            queue.push(Prio::new("seventh", Some(0), LockStatus::Unlocked)).unwrap();
 
             assert_eq!(
                 vec!["fourth", "fifth", "sixth", "seventh", "second", "third", "first"],
@@ -146,6 +147,1620 @@ pub mod tests {
         assert_eq!(queue.percentage_full().round(), 60.0);
     }
 
+    pub mod id_index {
+        use crate::queue::{error::QueueError, prelude::Prio, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn retrieve_and_pop_by_id() {
+            let mut queue = Queue::new();
+
+            let first = queue.push(Prio::wolock("first", Some(3))).unwrap();
+            let second = queue.push(Prio::wolock("second", Some(0))).unwrap();
+            let third = queue.push(Prio::wolock("third", Some(1))).unwrap();
+
+            assert_eq!(Ok("first"), queue.retrieve_by_id(first));
+            assert_eq!(Ok("third"), queue.retrieve_by_id(third));
+
+            assert_eq!(Some("second"), queue.pop());
+            assert!(queue.retrieve_by_id(second).is_err());
+
+            assert_eq!(Some("first"), queue.pop_by_id(first));
+            assert_eq!(None, queue.pop_by_id(first));
+            assert!(queue.retrieve_by_id(first).is_err());
+
+            assert_eq!(vec!["third"], queue.get_elements());
+        }
+
+        #[test]
+        fn sized_pop_by_id() {
+            let mut queue = SizedQueue::new(2);
+
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.push(Prio::wolock("second", Some(1))).unwrap();
+
+            assert_eq!(Some("first"), queue.pop_by_id(first));
+            assert!(queue.push(Prio::wolock("third", Some(2))).is_ok());
+            assert_eq!(vec!["second", "third"], queue.get_elements());
+        }
+
+        #[test]
+        fn ids_are_never_reused() {
+            let mut queue = Queue::new();
+
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.pop();
+            let second = queue.push(Prio::wolock("second", Some(0))).unwrap();
+
+            assert_ne!(first, second);
+            assert_eq!(first.seq() + 1, second.seq());
+            assert_eq!(None, queue.pop_by_id(first));
+            assert_eq!(Ok("second"), queue.retrieve_by_id(second));
+        }
+
+        #[test]
+        fn foreign_ids_are_rejected() {
+            let mut queue = Queue::new();
+            let mut other = Queue::new();
+            other.set_dead_letter(1);
+
+            let ours = queue.push(Prio::wolock("ours", Some(0))).unwrap();
+            let theirs = other.push(Prio::wolock("theirs", Some(0))).unwrap();
+
+            assert!(queue.owns(ours) && !queue.owns(theirs));
+            assert!(other.dead_letters().unwrap().owns(theirs));
+            assert_eq!(Err(QueueError::ForeignId(theirs)), queue.retrieve_by_id(theirs));
+            assert_eq!(Err(QueueError::ForeignId(theirs)), queue.lock(theirs));
+            assert_eq!(None, queue.pop_by_id(theirs));
+            assert_eq!(Ok("ours"), queue.retrieve_by_id(ours));
+
+            queue.set_dead_letter(1);
+            assert_eq!(None, queue.drop_dead_letter(theirs));
+            assert_eq!(Err(QueueError::ForeignId(theirs)), queue.requeue_dead_letter(theirs));
+            assert_eq!(1, other.size());
+        }
+    }
+
+    pub mod traits {
+        use std::time::Duration;
+
+        use crate::queue::{
+            error::QueueError,
+            prelude::{Direction, LockStatus, OrderPolicy, Prio, ShrinkPolicy, TieBreak},
+            sized::SizedQueue,
+            traits::{BoundedQueue, PriorityQueue},
+            Queue,
+        };
+
+
+        fn fill<Q: PriorityQueue<&'static str> + ?Sized>(queue: &mut Q) -> Vec<&'static str> {
+            queue.push(Prio::wolock("second", Some(1))).unwrap();
+            queue.push(Prio::new("first", Some(0), LockStatus::Locked)).unwrap();
+            let id = queue.push_non_prio("third").unwrap();
+
+            assert!(queue.owns(id));
+            assert_eq!(Ok("third"), queue.retrieve_by_id(id));
+            assert_eq!(Some(("first", 1)), queue.peek().map(|(item, id)| (*item, id.seq())));
+            assert_eq!(3, queue.all().len());
+
+            let mut popped = Vec::new();
+            while let Some(item) = queue.pop() {
+                popped.push(item);
+            }
+            assert!(queue.is_empty());
+            popped
+        }
+
+        #[test]
+        fn same_semantics() {
+            let expected = vec!["first", "second", "third"];
+            assert_eq!(expected, fill(&mut Queue::new()));
+            assert_eq!(expected, fill(&mut SizedQueue::new(3)));
+        }
+
+        #[test]
+        fn trait_objects() {
+            let mut queues: Vec<Box<dyn PriorityQueue<&'static str>>> = vec![Box::new(Queue::new()), Box::new(SizedQueue::new(3))];
+            for queue in &mut queues {
+                assert_eq!(vec!["first", "second", "third"], fill(queue.as_mut()));
+
+                queue.set_dead_letter(1);
+                queue.push(Prio::wolip("failing")).unwrap();
+                let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+                queue.nack(lease).unwrap();
+                assert_eq!(Some("failing"), queue.dead_letters().unwrap().peek().map(|(item, _)| *item));
+            }
+        }
+
+        #[test]
+        fn bounded_push_non_prio() {
+            let mut queue = SizedQueue::new(2);
+
+            queue.push_non_prio("first").unwrap();
+            queue.push_non_prio("second").unwrap();
+            assert!(queue.is_full());
+            assert_eq!(100.0, BoundedQueue::percentage_full(&queue));
+
+            let err = queue.push_non_prio("third").unwrap_err();
+            assert_eq!(QueueError::Full(Prio::new("third", Some(3), LockStatus::Unlocked)), err);
+            assert_eq!(vec!["first", "second"], queue.get_elements());
+        }
+
+        #[test]
+        fn push_non_prio_goes_last_in_both_directions() {
+            for direction in [Direction::MinFirst, Direction::MaxFirst] {
+                let order = OrderPolicy::new(direction, TieBreak::Fifo);
+                let mut queue = Queue::with_order(order);
+                let mut sized = SizedQueue::with_order(5, order);
+                for queue in [&mut queue as &mut dyn PriorityQueue<&str>, &mut sized] {
+                    queue.push(Prio::wolock("a", Some(0))).unwrap();
+                    queue.push(Prio::wolock("b", Some(0))).unwrap();
+                    queue.push_non_prio("np").unwrap();
+                    assert_eq!(vec!["a", "b", "np"], queue.get_elements());
+                }
+            }
+        }
+
+        #[test]
+        fn bounded_resize() {
+            fn halve<Q: BoundedQueue<u8>>(queue: &mut Q) -> usize {
+                let max_size = queue.max_size() / 2;
+                queue.resize(max_size, ShrinkPolicy::Truncate).unwrap().len()
+            }
+
+            let mut queue = SizedQueue::new(4);
+            for i in 0..4 {
+                queue.push(Prio::wolip(i)).unwrap();
+            }
+
+            assert_eq!(2, halve(&mut queue));
+            assert_eq!(vec![0, 1], queue.get_elements());
+            assert!(queue.is_full());
+        }
+    }
+
+    pub mod iterators {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{prelude::{LockStatus, Prio}, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn iter_in_pop_order() {
+            let mut queue = Queue::new();
+            queue.push(Prio::wolock("second", Some(1))).unwrap();
+            queue.push(Prio::new("first", Some(1), LockStatus::Locked)).unwrap();
+            queue.push(Prio::wolock("third", Some(2))).unwrap();
+            queue.push_delayed(Prio::wolock("later", Some(0)), Duration::from_secs(60)).unwrap();
+
+            let borrowed: Vec<_> = queue.iter().map(|(flag, item)| (flag.priority, *item)).collect();
+            assert_eq!(vec![(1, "first"), (1, "second"), (2, "third")], borrowed);
+
+            for (_, item) in &mut queue {
+                if *item == "third" {
+                    *item = "last";
+                }
+            }
+            assert_eq!(vec!["first", "second", "last"], queue.get_elements());
+
+            let owned: Vec<_> = queue.into_iter().map(|(_, item)| item).collect();
+            assert_eq!(vec!["later", "first", "second", "last"], owned);
+        }
+
+        #[test]
+        fn iter_merges_due_elements() {
+            let mut queue = Queue::new();
+            queue.push(Prio::wolock("a", Some(0))).unwrap();
+            queue.push(Prio::wolock("expired", Some(1)).with_ttl(Duration::from_millis(5))).unwrap();
+            queue.push(Prio::wolock("c", Some(2))).unwrap();
+            queue.push(Prio::wolock("e", Some(4))).unwrap();
+            queue.push_delayed(Prio::wolock("b", Some(1)), Duration::from_millis(5)).unwrap();
+            queue.push_delayed(Prio::wolock("d", Some(3)), Duration::from_millis(5)).unwrap();
+            queue.push_delayed(Prio::wolock("later", Some(0)), Duration::from_secs(60)).unwrap();
+            thread::sleep(Duration::from_millis(20));
+
+            let forward: Vec<_> = queue.iter().map(|(_, item)| *item).collect();
+            assert_eq!(vec!["a", "b", "c", "d", "e"], forward);
+            let backward: Vec<_> = queue.iter().rev().map(|(_, item)| *item).collect();
+            assert_eq!(vec!["e", "d", "c", "b", "a"], backward);
+
+            let mut both = queue.iter();
+            assert_eq!(Some("a"), both.next().map(|(_, item)| *item));
+            assert_eq!(Some("e"), both.next_back().map(|(_, item)| *item));
+            assert_eq!(Some("d"), both.next_back().map(|(_, item)| *item));
+            assert_eq!(Some("b"), both.next().map(|(_, item)| *item));
+            assert_eq!(Some("c"), both.next().map(|(_, item)| *item));
+            assert_eq!(None, both.next_back());
+            assert_eq!(None, both.next());
+        }
+
+        #[test]
+        fn drain_leaves_delayed() {
+            let mut queue = SizedQueue::new(3);
+            queue.push(Prio::wolock(2, Some(2))).unwrap();
+            queue.push(Prio::wolock(1, Some(1))).unwrap();
+            let later = queue.push_delayed(Prio::wolock(0, Some(0)), Duration::from_secs(60)).unwrap();
+
+            let drained: Vec<_> = queue.drain().map(|(_, item)| item).collect();
+            assert_eq!(vec![1, 2], drained);
+            assert_eq!(1, queue.size());
+            assert_eq!(Ok(0), queue.retrieve_by_id(later));
+            assert_eq!(None, queue.pop());
+        }
+
+        #[test]
+        fn collect_and_extend() {
+            let mut queue: Queue<&str> = [Prio::wolock("b", Some(1)), Prio::wolock("a", Some(0))].into_iter().collect();
+            queue.extend([Prio::wolock("c", Some(2))]);
+            assert_eq!(vec!["a", "b", "c"], queue.get_elements());
+
+            let mut sized: SizedQueue<&str> = queue.into_iter().map(|(flag, item)| Prio::wolock(item, Some(flag.priority))).collect();
+            assert_eq!(3, sized.max_size());
+            assert_eq!(vec!["a", "b", "c"], sized.iter().map(|(_, item)| *item).collect::<Vec<_>>());
+            assert!(sized.push(Prio::wolip("d")).is_err());
+        }
+
+        #[test]
+        #[should_panic(expected = "queue is full")]
+        fn extend_past_max_size() {
+            let mut queue = SizedQueue::new(1);
+            queue.extend([Prio::wolip(1), Prio::wolip(2)]);
+        }
+    }
+
+    pub mod peek_mut {
+        use crate::queue::{
+            peek::PeekMut,
+            prelude::{FifoPosition, LockStatus, Prio},
+            sized::SizedQueue,
+            Queue,
+        };
+
+
+        #[test]
+        fn empty_queue() {
+            let mut queue: Queue<&str> = Queue::new();
+            assert_eq!(None, queue.peek());
+            assert!(queue.peek_mut().is_none());
+
+            queue.push(Prio::wolip("only")).unwrap();
+            queue.pop();
+            assert_eq!(None, queue.peek());
+            assert_eq!(None, SizedQueue::<&str>::new(1).peek());
+        }
+
+        #[test]
+        fn change_head_in_place() {
+            let mut queue = Queue::new();
+            let first = queue.push(Prio::wolock("first".to_string(), Some(0))).unwrap();
+            queue.push(Prio::wolock("second".to_string(), Some(1))).unwrap();
+
+            {
+                let mut head = queue.peek_mut().unwrap();
+                assert_eq!(first, PeekMut::id(&head));
+                head.push_str(" (retried)");
+                PeekMut::reprioritize(&mut head, 1, FifoPosition::Reset);
+                assert_eq!(1, PeekMut::flag(&head).priority);
+            }
+
+            assert_eq!(vec!["second", "first (retried)"], queue.get_elements());
+            assert_eq!(Ok("first (retried)".to_string()), queue.retrieve_by_id(first));
+
+            let mut head = queue.peek_mut().unwrap();
+            PeekMut::set_lock(&mut head, LockStatus::Locked);
+            drop(head);
+            assert_eq!(LockStatus::Locked, queue.all().keys().next().unwrap().locked);
+        }
+
+        #[test]
+        fn pop_through_handle() {
+            let mut queue = Queue::new();
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.push(Prio::wolock("second", Some(1))).unwrap();
+
+            let mut head = queue.peek_mut().unwrap();
+            PeekMut::reprioritize(&mut head, 2, FifoPosition::Keep);
+            assert_eq!("first", PeekMut::pop(head));
+
+            assert_eq!(1, queue.size());
+            assert!(queue.retrieve_by_id(first).is_err());
+            assert_eq!(Some("second"), queue.pop());
+        }
+    }
+
+    pub mod reprioritize {
+        use crate::queue::{prelude::{FifoPosition, LockStatus, Prio}, Queue};
+
+
+        #[test]
+        fn keeps_identifier() {
+            let mut queue = Queue::new();
+
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            let second = queue.push(Prio::wolock("second", Some(1))).unwrap();
+            let third = queue.push(Prio::wolock("third", Some(2))).unwrap();
+
+            queue.reprioritize(third, 0, FifoPosition::Keep).unwrap();
+            assert_eq!(vec!["first", "third", "second"], queue.get_elements());
+            assert_eq!(Ok("third"), queue.retrieve_by_id(third));
+
+            queue.reprioritize(first, 5, FifoPosition::Keep).unwrap();
+            assert_eq!(vec!["third", "second", "first"], queue.get_elements());
+
+            assert_eq!(Some(("third", third)), queue.peek().map(|(item, id)| (*item, id)));
+            assert_eq!(Some("second"), queue.pop_by_id(second));
+            assert!(queue.reprioritize(second, 0, FifoPosition::Keep).is_err());
+        }
+
+        #[test]
+        fn lock_and_unlock() {
+            let mut queue = Queue::new();
+
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            let second = queue.push(Prio::wolock("second", Some(0))).unwrap();
+
+            queue.lock(second).unwrap();
+            assert_eq!(vec!["second", "first"], queue.get_elements());
+            assert_eq!(LockStatus::Locked, queue.all().keys().next().unwrap().locked);
+
+            queue.unlock(second).unwrap();
+            assert_eq!(vec!["first", "second"], queue.get_elements());
+            assert_eq!(Ok("first"), queue.retrieve_by_id(first));
+        }
+    }
+
+    pub mod generic_priority {
+        use crate::queue::{
+            prelude::{AdmissionBand, FifoPosition, Prio},
+            sized::SizedQueue,
+            Queue,
+        };
+
+
+        #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+        enum Severity {
+            Critical,
+            Warning,
+            Info,
+        }
+
+        #[test]
+        fn enum_priorities_with_locks() {
+            let mut queue: Queue<&str, Severity> = Queue::default();
+
+            queue.push(Prio::wolock("disk almost full", Some(Severity::Warning))).unwrap();
+            queue.push(Prio::wolock("user logged in", Some(Severity::Info))).unwrap();
+            let fire = queue.push(Prio::wolock("datacenter on fire", Some(Severity::Info))).unwrap();
+            queue.push(Prio::wlock("pinned warning", Some(Severity::Warning))).unwrap();
+
+            queue.reprioritize(fire, Severity::Critical, FifoPosition::Keep).unwrap();
+            assert_eq!(
+                vec!["datacenter on fire", "pinned warning", "disk almost full", "user logged in"],
+                queue.get_elements()
+            );
+        }
+
+        #[test]
+        fn signed_and_tuple_priorities() {
+            let mut signed: Queue<&str, i32> = Queue::default();
+            signed.push(Prio::wolock("zero", Some(0))).unwrap();
+            signed.push(Prio::wolock("behind", Some(5))).unwrap();
+            signed.push(Prio::wolock("ahead", Some(-5))).unwrap();
+            assert_eq!(Some("ahead"), signed.pop());
+
+            let mut tiered: SizedQueue<&str, (u8, u64)> = SizedQueue::with_max_size(2);
+            tiered.push(Prio::wolock("free, soon", Some((2, 10)))).unwrap();
+            tiered.push(Prio::wolock("paid, later", Some((1, 99)))).unwrap();
+            assert!(tiered.push(Prio::wolock("paid, now", Some((1, 0)))).is_err());
+            assert_eq!(vec!["paid, later", "free, soon"], tiered.get_elements());
+        }
+
+        #[test]
+        fn priorities_that_are_not_copy() {
+            let mut queue: SizedQueue<&str, (u8, String)> = SizedQueue::with_max_size(4);
+            queue.set_admission_bands(vec![AdmissionBand::new((2, String::new()), 50)]);
+
+            queue.push(Prio::wolock("free", Some((2, "acme".to_string())))).unwrap();
+            let late = queue.push(Prio::wolock("late", Some((2, "zeta".to_string())))).unwrap();
+            assert!(queue.push(Prio::wolock("rejected", Some((3, "acme".to_string())))).is_err());
+            queue.push(Prio::wlock("paid", Some((1, "acme".to_string())))).unwrap();
+
+            queue.reprioritize(late, (0, "zeta".to_string()), FifoPosition::Keep).unwrap();
+            assert_eq!(vec!["late", "paid", "free"], queue.get_elements());
+            assert_eq!(Some("late"), queue.pop());
+        }
+
+        #[test]
+        fn admission_bands_over_enums() {
+            let mut queue: SizedQueue<u32, Severity> = SizedQueue::with_max_size(4);
+            queue.set_admission_bands(vec![AdmissionBand::new(Severity::Warning, 50)]);
+
+            queue.push(Prio::wolock(0, Some(Severity::Info))).unwrap();
+            queue.push(Prio::wolock(1, Some(Severity::Warning))).unwrap();
+            let err = queue.push(Prio::wolock(2, Some(Severity::Info))).unwrap_err();
+            assert_eq!("priorities Warning and up are limited to 50% of the queue", err.to_string());
+            assert!(queue.push(Prio::wolock(3, Some(Severity::Critical))).is_ok());
+        }
+    }
+
+    pub mod ordering {
+        use crate::queue::{
+            prelude::{Direction, OrderPolicy, OverflowPolicy, Prio, TieBreak},
+            sized::SizedQueue,
+            Queue,
+        };
+
+
+        #[test]
+        fn max_first_keeps_locks_ahead() {
+            let mut queue = Queue::with_order(OrderPolicy::new(Direction::MaxFirst, TieBreak::Fifo));
+
+            queue.push(Prio::wolock("low", Some(1))).unwrap();
+            queue.push(Prio::wolock("high", Some(9))).unwrap();
+            queue.push(Prio::wlock("pinned high", Some(9))).unwrap();
+            queue.push(Prio::wolock("middle", Some(5))).unwrap();
+
+            assert_eq!(vec!["pinned high", "high", "middle", "low"], queue.get_elements());
+            assert_eq!(Direction::MaxFirst, queue.order().direction);
+        }
+
+        #[test]
+        fn lifo_and_random_ties() {
+            let mut lifo = Queue::with_order(OrderPolicy::new(Direction::MinFirst, TieBreak::Lifo));
+            for i in 0..4 {
+                lifo.push(Prio::wolock(i, Some(0))).unwrap();
+            }
+            lifo.push(Prio::wolock(9, Some(1))).unwrap();
+            assert_eq!(vec![3, 2, 1, 0, 9], lifo.get_elements());
+
+            let mut random = Queue::with_order(OrderPolicy::new(Direction::MinFirst, TieBreak::Random));
+            for i in 0..50 {
+                random.push(Prio::wolock(i, Some(0))).unwrap();
+            }
+            let mut popped: Vec<u32> = std::iter::from_fn(|| random.pop()).collect();
+            assert_ne!((0..50).collect::<Vec<_>>(), popped);
+            popped.sort();
+            assert_eq!((0..50).collect::<Vec<_>>(), popped);
+        }
+
+        #[test]
+        fn eviction_follows_direction() {
+            let mut queue = SizedQueue::with_order(2, OrderPolicy::new(Direction::MaxFirst, TieBreak::Fifo));
+            queue.set_overflow_policy(OverflowPolicy::EvictLowest);
+
+            queue.push(Prio::wolock("urgent", Some(8))).unwrap();
+            queue.push(Prio::wolock("routine", Some(2))).unwrap();
+            assert!(queue.push_evicting(Prio::wolock("trivial", Some(1))).is_err());
+
+            let (_, evicted) = queue.push_evicting(Prio::wolock("important", Some(5))).unwrap();
+            assert_eq!(Some("routine"), evicted.map(|(_, item)| item));
+            assert_eq!(vec!["urgent", "important"], queue.get_elements());
+        }
+    }
+
+    pub mod clock {
+        use std::{sync::Arc, time::Duration};
+
+        use crate::queue::{
+            clock::ManualClock,
+            prelude::{AgingPolicy, FifoPosition, Prio},
+            sized::SizedQueue,
+            Queue,
+        };
+
+
+        #[test]
+        fn timestamps_decide_ties() {
+            let clock = Arc::new(ManualClock::new(200));
+            let mut queue = Queue::new().with_clock(clock.clone());
+
+            queue.push(Prio::wolock("stamped later", Some(1))).unwrap();
+            clock.set(100);
+            queue.push(Prio::wolock("stamped earlier", Some(1))).unwrap();
+
+            assert_eq!(vec!["stamped earlier", "stamped later"], queue.get_elements());
+            assert_eq!(Some(100), queue.all().keys().map(|flag| flag.timestamp).min());
+        }
+
+        #[test]
+        fn reset_moves_behind_equals() {
+            let clock = Arc::new(ManualClock::default());
+            let mut queue = SizedQueue::new(4).with_clock(clock.clone());
+
+            let first = queue.push(Prio::wolock("first", Some(2))).unwrap();
+            clock.advance(Duration::from_nanos(1));
+            queue.push(Prio::wolock("second", Some(2))).unwrap();
+            clock.advance(Duration::from_nanos(1));
+
+            queue.reprioritize(first, 2, FifoPosition::Keep).unwrap();
+            assert_eq!(vec!["first", "second"], queue.get_elements());
+            queue.reprioritize(first, 2, FifoPosition::Reset).unwrap();
+            assert_eq!(vec!["second", "first"], queue.get_elements());
+        }
+
+        #[test]
+        fn aging_follows_the_clock() {
+            let clock = Arc::new(ManualClock::default());
+            let mut queue = Queue::new().with_clock(clock.clone());
+            queue.set_aging(Some(AgingPolicy::new(Duration::from_millis(10))));
+
+            let waiting = queue.push(Prio::wolock("waiting", Some(5))).unwrap();
+            clock.advance(Duration::from_millis(30));
+            queue.push(Prio::wolock("fresh", Some(3))).unwrap();
+
+            queue.age();
+            assert_eq!(vec!["waiting", "fresh"], queue.get_elements());
+            assert_eq!(Some(2), queue.all().keys().find(|flag| flag.identifier == waiting).map(|flag| flag.priority));
+        }
+
+        #[test]
+        fn each_element_ages_on_its_own_schedule() {
+            let clock = Arc::new(ManualClock::default());
+            let mut queue = Queue::new().with_clock(clock.clone());
+            queue.set_aging(Some(AgingPolicy::new(Duration::from_millis(10))));
+            let priority = |queue: &Queue<&str>, id| queue.all().keys().find(|flag| flag.identifier == id).map(|flag| flag.priority);
+
+            let first = queue.push(Prio::wolock("first", Some(1))).unwrap();
+            clock.advance(Duration::from_millis(5));
+            let second = queue.push(Prio::wolock("second", Some(5))).unwrap();
+
+            clock.advance(Duration::from_millis(6));
+            queue.age();
+            assert_eq!((Some(0), Some(5)), (priority(&queue, first), priority(&queue, second)));
+
+            clock.advance(Duration::from_millis(24));
+            queue.age();
+            assert_eq!((Some(0), Some(2)), (priority(&queue, first), priority(&queue, second)));
+
+            queue.lock(second).unwrap();
+            clock.advance(Duration::from_millis(50));
+            queue.age();
+            assert_eq!(Some(2), priority(&queue, second));
+        }
+    }
+
+    pub mod errors {
+        use crate::queue::{error::QueueError, prelude::Prio, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn missing_priority() {
+            let mut queue = Queue::new();
+
+            let err = queue.push(Prio::wolock("first", None)).unwrap_err();
+            assert_eq!(QueueError::MissingPriority(Prio::wolock("first", None)), err);
+            assert_eq!(0, queue.size());
+        }
+
+        #[test]
+        fn empty_and_not_found() {
+            let mut queue = Queue::new();
+            let id = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.pop();
+            assert_eq!(Err(QueueError::Empty), queue.retrieve_by_id(id));
+
+            queue.push(Prio::wolock("second", Some(0))).unwrap();
+            assert_eq!(Err(QueueError::NotFound(id)), queue.retrieve_by_id(id));
+            assert_eq!(Err(QueueError::NotFound(id)), queue.lock(id));
+        }
+
+        #[test]
+        fn full_hands_item_back() {
+            let mut queue = SizedQueue::new(1);
+            queue.push(Prio::wolock("first", Some(0))).unwrap();
+
+            let err = queue.push(Prio::wlock("second", Some(1))).unwrap_err();
+            assert_eq!("queue is full", err.to_string());
+
+            let item = err.into_item().unwrap();
+            queue.pop();
+            assert!(queue.push(item).is_ok());
+            assert_eq!(vec!["second"], queue.get_elements());
+        }
+    }
+
+    pub mod overflow {
+        use crate::queue::{error::QueueError, prelude::{OverflowPolicy, Prio}, sized::SizedQueue};
+
+
+        #[test]
+        fn evict_lowest_spares_locked() {
+            let mut queue = SizedQueue::new(3);
+            queue.set_overflow_policy(OverflowPolicy::EvictLowest);
+
+            queue.push(Prio::wolock("low", Some(5))).unwrap();
+            let middle = queue.push(Prio::wolock("middle", Some(3))).unwrap();
+            queue.push(Prio::wlock("pinned", Some(9))).unwrap();
+
+            let (_, evicted) = queue.push_evicting(Prio::wolock("urgent", Some(0))).unwrap();
+            assert_eq!(Some("low"), evicted.map(|(_, item)| item));
+
+            let err = queue.push_evicting(Prio::wolock("late", Some(3))).unwrap_err();
+            assert_eq!(QueueError::Full(Prio::wolock("late", Some(3))), err);
+
+            let (_, evicted) = queue.push_evicting(Prio::wolock("sooner", Some(1))).unwrap();
+            assert_eq!(Some((middle, "middle")), evicted);
+            assert_eq!(vec!["urgent", "sooner", "pinned"], queue.get_elements());
+
+            assert!(queue.push_evicting(Prio::wolock("never", Some(2))).is_err());
+        }
+
+        #[test]
+        fn drop_oldest_and_newest() {
+            let mut queue = SizedQueue::new(2);
+            queue.set_overflow_policy(OverflowPolicy::DropOldest);
+
+            queue.push(Prio::wolock("first", Some(1))).unwrap();
+            queue.push(Prio::wolock("second", Some(0))).unwrap();
+            let (_, evicted) = queue.push_evicting(Prio::wolock("third", Some(2))).unwrap();
+            assert_eq!(Some("first"), evicted.map(|(_, item)| item));
+
+            queue.set_overflow_policy(OverflowPolicy::DropNewest);
+            let (_, evicted) = queue.push_evicting(Prio::wolock("fourth", Some(2))).unwrap();
+            assert_eq!(Some("third"), evicted.map(|(_, item)| item));
+            assert_eq!(vec!["second", "fourth"], queue.get_elements());
+        }
+
+        #[test]
+        fn reject_by_default() {
+            let mut queue = SizedQueue::new(1);
+            assert_eq!(OverflowPolicy::Reject, queue.overflow_policy());
+
+            let (_, evicted) = queue.push_evicting(Prio::wolock("first", Some(1))).unwrap();
+            assert_eq!(None, evicted);
+            assert!(queue.push_evicting(Prio::wolock("second", Some(0))).is_err());
+            assert_eq!(1, queue.size());
+        }
+    }
+
+    pub mod resize {
+        use crate::queue::{error::QueueError, prelude::{Prio, ShrinkPolicy}, sized::SizedQueue};
+
+
+        #[test]
+        fn refuse_and_truncate() {
+            let mut queue = SizedQueue::new(4);
+            queue.push(Prio::wolock("first", Some(0))).unwrap();
+            let second = queue.push(Prio::wolock("second", Some(3))).unwrap();
+            queue.push(Prio::wlock("pinned", Some(9))).unwrap();
+            let third = queue.push(Prio::wolock("third", Some(2))).unwrap();
+
+            assert_eq!(Err(QueueError::Overfull(4)), queue.resize(2, ShrinkPolicy::Refuse));
+            assert_eq!(4, queue.max_size());
+            assert_eq!(Err(QueueError::Overfull(4)), queue.resize(0, ShrinkPolicy::Truncate));
+            assert_eq!(4, queue.size());
+
+            let truncated = queue.resize(2, ShrinkPolicy::Truncate).unwrap();
+            assert_eq!(vec![(second, "second"), (third, "third")], truncated);
+            assert_eq!(vec!["first", "pinned"], queue.get_elements());
+            assert_eq!(2, queue.max_size());
+
+            assert_eq!(Ok(Vec::new()), queue.resize(8, ShrinkPolicy::Refuse));
+            assert_eq!(8, queue.max_size());
+        }
+
+        #[test]
+        fn allow_overflow_until_drained() {
+            let mut queue = SizedQueue::new(3);
+            for i in 0..3 {
+                queue.push(Prio::wolock(i, Some(i))).unwrap();
+            }
+
+            assert_eq!(Ok(Vec::new()), queue.resize(1, ShrinkPolicy::AllowOverflow));
+            assert_eq!(300.0, queue.percentage_full().round());
+            assert!(queue.push(Prio::wolip(3)).is_err());
+
+            queue.pop();
+            queue.pop();
+            assert!(queue.push(Prio::wolip(3)).is_err());
+            queue.pop();
+            assert!(queue.push(Prio::wolip(3)).is_ok());
+        }
+
+        #[test]
+        fn expand_checks_overflow() {
+            let mut queue = SizedQueue::<u32>::new(u32::MAX - 1);
+
+            assert_eq!(Ok(()), queue.expand(1));
+            assert_eq!(Err(QueueError::CapacityOverflow), queue.expand(1));
+            assert_eq!(u32::MAX, queue.max_size());
+
+            let mut queue = SizedQueue::<u32>::new(0);
+            assert_eq!(Err(QueueError::CapacityOverflow), queue.expand(u32::MAX as usize + 1));
+            assert_eq!(0, queue.max_size());
+        }
+    }
+
+    pub mod admission {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{error::QueueError, prelude::{AdmissionBand, FifoPosition, Prio}, sized::SizedQueue};
+
+
+        #[test]
+        fn band_keeps_room_for_urgent() {
+            let mut queue = SizedQueue::new(10);
+            queue.set_admission_bands(vec![AdmissionBand::new(6, 70)]);
+
+            for i in 0..7 {
+                queue.push(Prio::wolock(i, Some(6 + i))).unwrap();
+            }
+            let err = queue.push(Prio::wolock(7, Some(9))).unwrap_err();
+            assert_eq!(QueueError::BandFull(AdmissionBand::new(6, 70), Prio::wolock(7, Some(9))), err);
+            assert_eq!("priorities 6 and up are limited to 70% of the queue", err.to_string());
+
+            for i in 0..3 {
+                queue.push(Prio::wolock(i, Some(5))).unwrap();
+            }
+            assert!(matches!(queue.push(Prio::wolock(0, Some(0))), Err(QueueError::Full(_))));
+        }
+
+        #[test]
+        fn nested_bands_report_the_one_hit() {
+            let mut queue = SizedQueue::new(10);
+            queue.set_admission_bands(vec![AdmissionBand::new(8, 20), AdmissionBand::new(4, 50)]);
+            assert_eq!(4, queue.admission_bands()[0].priority);
+
+            queue.push(Prio::wolock("a", Some(9))).unwrap();
+            queue.push(Prio::wolock("b", Some(8))).unwrap();
+            let err = queue.push(Prio::wolock("c", Some(8))).unwrap_err();
+            assert!(matches!(err, QueueError::BandFull(AdmissionBand { priority: 8, .. }, _)));
+
+            for _ in 0..3 {
+                queue.push(Prio::wolock("d", Some(4))).unwrap();
+            }
+            let err = queue.push(Prio::wolock("e", Some(5))).unwrap_err();
+            assert!(matches!(err, QueueError::BandFull(AdmissionBand { priority: 4, .. }, _)));
+        }
+
+        #[test]
+        fn room_frees_up_as_band_drains() {
+            let mut queue = SizedQueue::new(4);
+            queue.set_admission_bands(vec![AdmissionBand::new(1, 50)]);
+
+            queue.push(Prio::wolock("first", Some(1))).unwrap();
+            queue.push(Prio::wolock("second", Some(2))).unwrap();
+            assert!(queue.push(Prio::wolock("third", Some(3))).is_err());
+
+            assert_eq!(Some("first"), queue.pop());
+            assert!(queue.push(Prio::wolock("third", Some(3))).is_ok());
+        }
+
+        #[test]
+        fn band_counts_follow_every_change() {
+            let mut queue = SizedQueue::new(4);
+            queue.set_admission_bands(vec![AdmissionBand::new(5, 50)]);
+
+            let moved = queue.push(Prio::wolock("moved", Some(5))).unwrap();
+            queue.push(Prio::wolock("short", Some(6)).with_ttl(Duration::from_millis(10))).unwrap();
+            assert!(queue.push(Prio::wolock("late", Some(7))).is_err());
+
+            thread::sleep(Duration::from_millis(20));
+            queue.push(Prio::wolock("late", Some(7))).unwrap();
+            assert!(queue.push(Prio::wolock("later", Some(8))).is_err());
+
+            queue.reprioritize(moved, 1, FifoPosition::Keep).unwrap();
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.push(Prio::wolock("later", Some(8))).unwrap();
+            assert!(queue.push(Prio::wolock("last", Some(9))).is_err());
+
+            queue.nack(lease).unwrap();
+            queue.drain().for_each(drop);
+            queue.push(Prio::wolock("a", Some(5))).unwrap();
+            queue.push(Prio::wolock("b", Some(5))).unwrap();
+            assert!(queue.push(Prio::wolock("c", Some(5))).is_err());
+        }
+    }
+
+    pub mod watermarks {
+        use std::sync::{Arc, Mutex};
+
+        use crate::queue::{prelude::{Prio, ShrinkPolicy, WatermarkEvent}, sized::SizedQueue};
+
+
+        #[test]
+        fn crossings_with_hysteresis() {
+            let mut queue = SizedQueue::new(10);
+            let events = queue.watermark_channel(80.0, 30.0);
+
+            for i in 0..9 {
+                queue.push(Prio::wolock(i, Some(i))).unwrap();
+            }
+            assert_eq!(vec![WatermarkEvent::High(80.0)], events.try_iter().collect::<Vec<_>>());
+
+            for _ in 0..4 {
+                queue.pop();
+            }
+            queue.push(Prio::wolip(9)).unwrap();
+            queue.push(Prio::wolip(10)).unwrap();
+            assert_eq!(0, events.try_iter().count());
+
+            for _ in 0..4 {
+                queue.pop();
+            }
+            assert_eq!(vec![WatermarkEvent::Low(30.0)], events.try_iter().collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn callback_and_resize() {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let mut queue = SizedQueue::new(4);
+            for i in 0..2 {
+                queue.push(Prio::wolock(i, Some(i))).unwrap();
+            }
+
+            let sink = Arc::clone(&seen);
+            queue.set_watermarks(100.0, 50.0, move |event| sink.lock().unwrap().push(event));
+            assert!(seen.lock().unwrap().is_empty());
+
+            queue.resize(2, ShrinkPolicy::Refuse).unwrap();
+            queue.expand(2).unwrap();
+            assert_eq!(vec![WatermarkEvent::High(100.0), WatermarkEvent::Low(50.0)], *seen.lock().unwrap());
+
+            queue.clear_watermarks();
+            queue.resize(2, ShrinkPolicy::Refuse).unwrap();
+            assert_eq!(2, seen.lock().unwrap().len());
+        }
+
+        #[test]
+        fn already_full_reports_right_away() {
+            let mut queue = SizedQueue::new(1);
+            queue.push(Prio::wolip("first")).unwrap();
+
+            let events = queue.watermark_channel(90.0, 10.0);
+            assert_eq!(Ok(WatermarkEvent::High(100.0)), events.try_recv());
+        }
+    }
+
+    pub mod aging {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{prelude::{AgingPolicy, LockStatus, Prio}, Queue};
+
+
+        #[test]
+        fn unlocked_items_move_up() {
+            let mut queue = Queue::new();
+            queue.set_aging(Some(AgingPolicy::new(Duration::from_secs(1))));
+
+            queue.push(Prio::new("waiting", Some(1), LockStatus::Unlocked)).unwrap();
+            queue.push(Prio::new("pinned", Some(1), LockStatus::Locked)).unwrap();
+            queue.push(Prio::new("low", Some(3), LockStatus::Unlocked)).unwrap();
+
+            thread::sleep(Duration::from_millis(1100));
+            queue.push(Prio::new("fresh", Some(0), LockStatus::Unlocked)).unwrap();
+
+            // "waiting" reached priority 0 before "fresh" arrived, "pinned" stayed at 1.
+            assert_eq!(vec!["waiting", "fresh", "pinned", "low"], queue.get_elements());
+            assert_eq!(0, queue.all().keys().next().unwrap().priority);
+        }
+
+        #[test]
+        fn disabled_by_default() {
+            let mut queue = Queue::new();
+
+            queue.push(Prio::wolock("waiting", Some(1))).unwrap();
+            thread::sleep(Duration::from_millis(1100));
+            queue.push(Prio::wolock("fresh", Some(0))).unwrap();
+
+            assert_eq!(vec!["fresh", "waiting"], queue.get_elements());
+        }
+    }
+
+    pub mod delayed {
+        use std::{
+            thread,
+            time::{Duration, Instant, SystemTime},
+        };
+
+        use crate::queue::{prelude::Prio, shared::SharedQueue, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn hidden_until_due() {
+            let mut queue = Queue::new();
+
+            let later = queue.push_delayed(Prio::wolip("later"), Duration::from_millis(50)).unwrap();
+            queue.push(Prio::wolock("now", Some(5))).unwrap();
+
+            assert_eq!(Some("now"), queue.peek().map(|(item, _)| *item));
+            assert_eq!(vec!["now"], queue.get_elements());
+            assert_eq!(2, queue.size());
+            assert_eq!(Ok("later"), queue.retrieve_by_id(later));
+            assert!(queue.next_due().unwrap() > Instant::now());
+
+            thread::sleep(Duration::from_millis(60));
+            assert_eq!(Some(("later", later)), queue.peek().map(|(item, id)| (*item, id)));
+            assert_eq!(vec!["later", "now"], queue.get_elements());
+            assert_eq!(Some("later"), queue.pop());
+            assert_eq!(None, queue.next_due());
+        }
+
+        #[test]
+        fn push_at_system_time() {
+            let mut queue = Queue::new();
+
+            let past = queue.push_at(Prio::wolip("past"), SystemTime::now() - Duration::from_secs(5)).unwrap();
+            let future = queue.push_at(Prio::wolip("future"), SystemTime::now() + Duration::from_secs(60)).unwrap();
+
+            assert_eq!(Some("past"), queue.pop());
+            assert_eq!(None, queue.pop());
+            assert_eq!(Some("future"), queue.pop_by_id(future));
+            assert_ne!(past, future);
+        }
+
+        #[test]
+        fn sized_counts_delayed() {
+            let mut queue = SizedQueue::new(1);
+
+            queue.push_delayed(Prio::wolip("later"), Duration::from_secs(60)).unwrap();
+            assert!(queue.push(Prio::wolip("now")).is_err());
+            assert_eq!(None, queue.pop());
+        }
+
+        #[test]
+        fn shared_pop_waits_until_due() {
+            let queue = SharedQueue::new();
+            let start = Instant::now();
+
+            queue.push_delayed(Prio::wolip("later"), Duration::from_millis(50)).unwrap();
+
+            assert_eq!(None, queue.try_pop());
+            assert_eq!("later", queue.pop());
+            assert!(start.elapsed() >= Duration::from_millis(50));
+        }
+    }
+
+    pub mod expiry {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{prelude::Prio, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn skipped_and_purged() {
+            let mut queue = Queue::new();
+
+            let stale = queue.push(Prio::wolip("stale").with_ttl(Duration::from_millis(20))).unwrap();
+            queue.push(Prio::wolock("fresh", Some(1)).with_ttl(Duration::from_secs(60))).unwrap();
+            queue.push(Prio::wolock("forever", Some(2))).unwrap();
+
+            assert_eq!(vec!["stale", "fresh", "forever"], queue.get_elements());
+            thread::sleep(Duration::from_millis(30));
+
+            assert_eq!(Some("fresh"), queue.peek().map(|(item, _)| *item));
+            assert_eq!(vec!["fresh", "forever"], queue.get_elements());
+            assert!(queue.retrieve_by_id(stale).is_err());
+            assert_eq!(2, queue.size());
+
+            assert_eq!(vec![(stale, "stale")], queue.purge_expired());
+            assert!(queue.purge_expired().is_empty());
+        }
+
+        #[test]
+        fn pop_drops_expired() {
+            let mut queue = Queue::new();
+
+            queue.push(Prio::wolip("stale").with_ttl(Duration::from_millis(20))).unwrap();
+            queue.push(Prio::wolock("fresh", Some(1))).unwrap();
+            thread::sleep(Duration::from_millis(30));
+
+            assert_eq!(Some("fresh"), queue.pop());
+            assert_eq!(None, queue.pop());
+            assert!(queue.purge_expired().is_empty());
+        }
+
+        #[test]
+        fn sized_counts_expired_as_free() {
+            let mut queue = SizedQueue::new(1);
+
+            queue.push(Prio::wolip("stale").with_ttl(Duration::from_millis(20))).unwrap();
+            assert!(queue.push(Prio::wolip("blocked")).is_err());
+            thread::sleep(Duration::from_millis(30));
+
+            assert_eq!(0.0, queue.percentage_full());
+            assert!(queue.push(Prio::wolip("fresh")).is_ok());
+            assert_eq!(vec!["fresh"], queue.get_elements());
+        }
+    }
+
+    pub mod leases {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{error::QueueError, prelude::Prio, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn ack_removes() {
+            let mut queue = Queue::new();
+
+            let first = queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.push(Prio::wolock("second", Some(1))).unwrap();
+
+            let (item, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            assert_eq!(("first", first), (item, lease.id()));
+            assert_eq!(vec!["second"], queue.get_elements());
+
+            queue.ack(lease).unwrap();
+            assert_eq!(Err(QueueError::LeaseExpired(first)), queue.ack(lease));
+            assert_eq!(1, queue.size());
+        }
+
+        #[test]
+        fn nack_requeues_in_place() {
+            let mut queue = Queue::new();
+
+            queue.push(Prio::wolock("first", Some(0))).unwrap();
+            queue.push(Prio::wolock("second", Some(0))).unwrap();
+
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.nack(lease).unwrap();
+
+            assert_eq!(vec!["first", "second"], queue.get_elements());
+            assert!(queue.nack(lease).is_err());
+        }
+
+        #[test]
+        fn expired_lease_requeues() {
+            let mut queue = SizedQueue::new(1);
+
+            queue.push(Prio::wolip("first")).unwrap();
+            let (_, lease) = queue.reserve(Duration::from_millis(20)).unwrap();
+
+            assert_eq!(None, queue.pop());
+            assert!(queue.push(Prio::wolip("second")).is_err());
+            thread::sleep(Duration::from_millis(30));
+
+            assert_eq!(Err(QueueError::LeaseExpired(lease.id())), queue.ack(lease));
+            let (item, renewed) = queue.reserve(Duration::from_secs(60)).unwrap();
+            assert_eq!(("first", lease.id()), (item, renewed.id()));
+            assert_ne!(lease, renewed);
+        }
+    }
+
+    pub mod dead_letter {
+        use std::time::Duration;
+
+        use crate::queue::{error::QueueError, prelude::Prio, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn moves_after_max_failures() {
+            let mut queue = Queue::new();
+            queue.set_dead_letter(2);
+
+            let id = queue.push(Prio::wolip("job")).unwrap();
+
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.nack(lease).unwrap();
+            assert_eq!(1, queue.failures(id));
+            assert_eq!(vec!["job"], queue.get_elements());
+
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.nack(lease).unwrap();
+            assert_eq!(0, queue.size());
+            assert_eq!(0, queue.failures(id));
+
+            let dead = queue.dead_letters().unwrap();
+            assert_eq!(Ok("job"), dead.retrieve_by_id(id));
+            assert_eq!(2, dead.failures(id));
+
+            queue.requeue_dead_letter(id).unwrap();
+            assert_eq!(Ok("job"), queue.retrieve_by_id(id));
+            assert_eq!(0, queue.dead_letters().unwrap().size());
+            assert_eq!(Err(QueueError::NotFound(id)), queue.requeue_dead_letter(id));
+        }
+
+        #[test]
+        fn drop_dead_letter() {
+            let mut queue = SizedQueue::new(1);
+            queue.set_dead_letter(1);
+
+            let id = queue.push(Prio::wolip("job")).unwrap();
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.nack(lease).unwrap();
+
+            queue.push(Prio::wolip("other")).unwrap();
+            assert_eq!(Err(QueueError::NoRoom(id)), queue.requeue_dead_letter(id));
+            assert_eq!(1, queue.dead_letters().unwrap().size());
+
+            assert_eq!(Some("job"), queue.drop_dead_letter(id));
+            assert_eq!(None, queue.drop_dead_letter(id));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub mod snapshots {
+        use std::time::Duration;
+
+        use crate::queue::{error::QueueError, prelude::{LockStatus, Prio}, sized::SizedQueue, Queue};
+
+
+        #[test]
+        fn queue_round_trip() {
+            let mut queue = Queue::new();
+            queue.set_dead_letter(1);
+
+            let first = queue.push(Prio::new("first".to_string(), Some(1), LockStatus::Locked)).unwrap();
+            queue.push(Prio::wolock("second".to_string(), Some(2))).unwrap();
+            let later = queue.push_delayed(Prio::wolip("later".to_string()), Duration::from_secs(60)).unwrap();
+            let dead = queue.push(Prio::wolip("dead".to_string())).unwrap();
+            let (_, lease) = queue.reserve(Duration::from_secs(60)).unwrap();
+            queue.nack(lease).unwrap();
+
+            let json = serde_json::to_string(&queue).unwrap();
+            let mut restored: Queue<String> = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(queue.all(), restored.all());
+            assert_eq!(vec!["first", "second"], restored.get_elements());
+            assert_eq!(Ok("later".to_string()), restored.retrieve_by_id(later));
+            assert!(restored.next_due().is_some());
+            assert_eq!(Some("dead".to_string()), restored.drop_dead_letter(dead));
+
+            let next = restored.push(Prio::wolip("next".to_string())).unwrap();
+            assert_eq!(queue.push(Prio::wolip("next".to_string())).unwrap(), next);
+            assert!(next > later && next > first);
+        }
+
+        #[test]
+        fn sized_round_trip() {
+            let mut queue = SizedQueue::new(2);
+            queue.push(Prio::wolip(1u8).with_ttl(Duration::from_secs(60))).unwrap();
+
+            let json = serde_json::to_string(&queue).unwrap();
+            let mut restored: SizedQueue<u8> = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(2, restored.max_size());
+            assert_eq!(vec![1], restored.get_elements());
+            restored.push(Prio::wolip(2)).unwrap();
+            assert!(restored.push(Prio::wolip(3)).is_err());
+        }
+
+        #[test]
+        fn exhausted_ids() {
+            let queue: Queue<u8> = Queue::new();
+            let json = serde_json::to_string(&queue).unwrap().replace("\"next_id\":0", &format!("\"next_id\":{}", u64::MAX));
+            let mut restored: Queue<u8> = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(Err(QueueError::IdsExhausted(Prio::wolip(1))), restored.push(Prio::wolip(1)));
+            assert_eq!(0, restored.size());
+        }
+    }
+
+    #[cfg(feature = "durable")]
+    pub mod durable {
+        use std::{
+            fs::{self, OpenOptions},
+            io::Write,
+            thread,
+            time::Duration,
+        };
+
+        use crate::queue::{
+            durable::{DurableError, DurableQueue, SyncPolicy},
+            prelude::{FifoPosition, Prio},
+        };
+
+
+        #[test]
+        fn reopen_replays_log() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let (first, third) = {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                let first = queue.push(Prio::wolock("first".to_string(), Some(1))).unwrap();
+                queue.push(Prio::wolock("second".to_string(), Some(0))).unwrap();
+                let third = queue.push(Prio::wolock("third".to_string(), Some(2))).unwrap();
+
+                assert_eq!(Some("second".to_string()), queue.pop().unwrap());
+                queue.reprioritize(third, 0, FifoPosition::Keep).unwrap();
+                (first, third)
+            };
+
+            let mut queue: DurableQueue<String> = DurableQueue::open(dir.path()).unwrap();
+            assert_eq!(vec!["third", "first"], queue.get_elements());
+            assert_eq!(Ok("first".to_string()), queue.retrieve_by_id(first));
+
+            let fourth = queue.push(Prio::wolip("fourth".to_string())).unwrap();
+            assert!(fourth > third);
+        }
+
+        #[test]
+        fn compaction_and_torn_records() {
+            let dir = tempfile::tempdir().unwrap();
+
+            {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                queue.set_sync_policy(SyncPolicy::Batched(10));
+                queue.set_compaction(Some(3));
+
+                let ids: Vec<_> = (0..4u32).map(|i| queue.push(Prio::wolock(i, Some(i))).unwrap()).collect();
+                assert_eq!(Some(0), queue.pop_by_id(ids[0]).unwrap());
+            }
+
+            let mut log = OpenOptions::new().append(true).open(dir.path().join("wal.log")).unwrap();
+            log.write_all(b"{\"Push\":{\"fl").unwrap();
+
+            {
+                let mut queue: DurableQueue<u32> = DurableQueue::open(dir.path()).unwrap();
+                assert_eq!(vec![1, 2, 3], queue.get_elements());
+                queue.push(Prio::wolock(4, Some(4))).unwrap();
+                queue.push(Prio::wolock(5, Some(5))).unwrap();
+            }
+
+            let queue: DurableQueue<u32> = DurableQueue::open(dir.path()).unwrap();
+            assert_eq!(vec![1, 2, 3, 4, 5], queue.get_elements());
+        }
+
+        #[test]
+        fn corrupt_whole_record_is_an_error() {
+            let dir = tempfile::tempdir().unwrap();
+            {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                queue.push(Prio::wolock(0u32, Some(0))).unwrap();
+            }
+
+            let mut log = OpenOptions::new().append(true).open(dir.path().join("wal.log")).unwrap();
+            log.write_all(b"{\"Push\":{\"fl\n").unwrap();
+            let len = log.metadata().unwrap().len();
+
+            assert!(matches!(DurableQueue::<u32>::open(dir.path()), Err(DurableError::Io(_))));
+            assert_eq!(len, fs::metadata(dir.path().join("wal.log")).unwrap().len());
+        }
+
+        #[test]
+        fn ttl_survives_reopen() {
+            let dir = tempfile::tempdir().unwrap();
+
+            {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                queue.push(Prio::wolock(0u8, Some(0)).with_ttl(Duration::from_millis(30))).unwrap();
+                queue.push(Prio::wolock(1, Some(1)).with_ttl(Duration::from_secs(60))).unwrap();
+            }
+
+            let mut queue: DurableQueue<u8> = DurableQueue::open(dir.path()).unwrap();
+            assert_eq!(vec![0, 1], queue.get_elements());
+
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(vec![1], queue.get_elements());
+            assert_eq!(Some(1), queue.pop().unwrap());
+        }
+
+        #[test]
+        fn ttl_keeps_running_after_compaction() {
+            let dir = tempfile::tempdir().unwrap();
+
+            {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                queue.push(Prio::wolock(0u8, Some(0)).with_ttl(Duration::from_millis(30))).unwrap();
+                queue.push(Prio::wolock(1, Some(1))).unwrap();
+                queue.compact().unwrap();
+            }
+            thread::sleep(Duration::from_millis(50));
+
+            let queue: DurableQueue<u8> = DurableQueue::open(dir.path()).unwrap();
+            assert_eq!(vec![1], queue.get_elements());
+            assert_eq!(1, queue.size());
+        }
+
+        #[test]
+        fn crash_between_snapshot_and_log_truncation() {
+            let dir = tempfile::tempdir().unwrap();
+            let log = dir.path().join("wal.log");
+
+            let old_log = {
+                let mut queue = DurableQueue::open(dir.path()).unwrap();
+                queue.push(Prio::wolock(0u8, Some(0)).with_ttl(Duration::from_millis(30))).unwrap();
+                queue.push(Prio::wolock(1, Some(1))).unwrap();
+                let old_log = fs::read(&log).unwrap();
+                queue.compact().unwrap();
+                old_log
+            };
+            fs::write(&log, old_log).unwrap();
+
+            let mut queue: DurableQueue<u8> = DurableQueue::open(dir.path()).unwrap();
+            assert_eq!(2, queue.size());
+            thread::sleep(Duration::from_millis(50));
+            assert_eq!(1, queue.size());
+            assert_eq!(Some(1), queue.pop().unwrap());
+            assert_eq!(0, queue.size());
+        }
+    }
+
+    #[cfg(feature = "spill")]
+    pub mod spill {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{
+            prelude::Prio,
+            spill::{SpillQueue, SpillThreshold},
+        };
+
+
+        #[test]
+        fn pops_in_order_across_spills() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = SpillQueue::new(dir.path(), SpillThreshold::Entries(4)).unwrap();
+
+            for i in (0..20u32).rev() {
+                queue.push(Prio::wolock(i, Some(i % 10))).unwrap();
+            }
+            assert_eq!(20, queue.size());
+            assert!(queue.spilled() > 0);
+
+            let mut popped = Vec::new();
+            while let Some(item) = queue.pop().unwrap() {
+                popped.push(item);
+            }
+            assert_eq!(vec![10, 0, 11, 1, 12, 2, 13, 3, 14, 4, 15, 5, 16, 6, 17, 7, 18, 8, 19, 9], popped);
+            assert_eq!(0, queue.size());
+        }
+
+        #[test]
+        fn peek_sees_spilled_head() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = SpillQueue::new(dir.path(), SpillThreshold::Bytes(64)).unwrap();
+
+            let first = queue.push(Prio::wolock("a".repeat(40), Some(1))).unwrap();
+            queue.push(Prio::wolock("b".repeat(40), Some(2))).unwrap();
+            queue.push(Prio::wolock("c".repeat(40), Some(3))).unwrap();
+            assert!(queue.spilled() > 0);
+
+            assert_eq!(Some(("a".repeat(40).as_str(), first)), queue.peek().unwrap().map(|(item, id)| (item.as_str(), id)));
+            queue.pop().unwrap();
+            assert_eq!(Some("b".repeat(40)), queue.peek().unwrap().map(|(item, _)| item.clone()));
+            assert_eq!(2, queue.size());
+        }
+
+        #[test]
+        fn pushes_merge_with_spilled_segments() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = SpillQueue::new(dir.path(), SpillThreshold::Entries(2)).unwrap();
+
+            for i in [5u32, 1, 7, 3, 9, 0] {
+                queue.push(Prio::wolock(i, Some(i))).unwrap();
+            }
+            assert_eq!(Some(0), queue.pop().unwrap());
+            assert_eq!(Some(1), queue.pop().unwrap());
+
+            queue.push(Prio::wolock(2, Some(2))).unwrap();
+            queue.push(Prio::wolock(8, Some(8))).unwrap();
+            assert_eq!(Some(&2), queue.peek().unwrap().map(|(item, _)| item));
+
+            let mut popped = Vec::new();
+            while let Some(item) = queue.pop().unwrap() {
+                popped.push(item);
+            }
+            assert_eq!(vec![2, 3, 5, 7, 8, 9], popped);
+            assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+        }
+
+        #[test]
+        fn expired_elements_dropped_in_memory_and_on_disk() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut queue = SpillQueue::new(dir.path(), SpillThreshold::Entries(2)).unwrap();
+
+            queue.push(Prio::wolock(0u32, Some(0)).with_ttl(Duration::from_millis(10))).unwrap();
+            queue.push(Prio::wolock(1, Some(1))).unwrap();
+            queue.push(Prio::wolock(2, Some(2)).with_ttl(Duration::from_millis(10))).unwrap();
+            queue.push(Prio::wolock(3, Some(3)).with_ttl(Duration::from_millis(10))).unwrap();
+            queue.push(Prio::wolock(4, Some(4))).unwrap();
+            assert!(queue.spilled() > 0);
+            assert_eq!(5, queue.size());
+
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(2, queue.size());
+            assert_eq!(Some(&1), queue.peek().unwrap().map(|(item, _)| item));
+            assert_eq!(Some(1), queue.pop().unwrap());
+            assert_eq!(Some(&4), queue.peek().unwrap().map(|(item, _)| item));
+            assert_eq!(Some(4), queue.pop().unwrap());
+            assert_eq!(None, queue.pop().unwrap());
+            assert_eq!(0, queue.size());
+        }
+
+        #[test]
+        fn segments_removed_on_drop() {
+            let dir = tempfile::tempdir().unwrap();
+            {
+                let mut queue = SpillQueue::new(dir.path(), SpillThreshold::Entries(2)).unwrap();
+                for i in 0..10u32 {
+                    queue.push(Prio::wolock(i, Some(i))).unwrap();
+                }
+                assert!(std::fs::read_dir(dir.path()).unwrap().next().is_some());
+            }
+            assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+        }
+    }
+
+    pub mod shared_queue {
+        use std::{thread, time::Duration};
+
+        use crate::queue::{prelude::{LockStatus, Prio}, shared::SharedQueue};
+
+
+        #[test]
+        fn ordering() {
+            let queue = SharedQueue::new();
+
+            queue.push(Prio::new("first", Some(2), LockStatus::Unlocked)).unwrap();
+            queue.push(Prio::new("second", Some(1), LockStatus::Unlocked)).unwrap();
+            queue.push(Prio::new("third", Some(1), LockStatus::Locked)).unwrap();
+
+            assert_eq!(Some("third"), queue.try_pop());
+            assert_eq!(Some("second"), queue.try_pop());
+            assert_eq!(Some("first"), queue.try_pop());
+            assert_eq!(None, queue.try_pop());
+        }
+
+        #[test]
+        fn pop_waits_for_push() {
+            let queue = SharedQueue::new();
+
+            let consumer = {
+                let queue = queue.clone();
+                thread::spawn(move || queue.pop())
+            };
+
+            thread::sleep(Duration::from_millis(50));
+            queue.push(Prio::wolip(7u32)).unwrap();
+
+            assert_eq!(7, consumer.join().unwrap());
+            assert_eq!(0, queue.size());
+        }
+
+        #[test]
+        fn pop_timeout() {
+            let queue: SharedQueue<u32> = SharedQueue::new();
+            assert_eq!(None, queue.pop_timeout(Duration::from_millis(10)));
+
+            queue.push(Prio::wolip(1)).unwrap();
+            assert_eq!(Some(1), queue.pop_timeout(Duration::from_millis(10)));
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub mod channel {
+        use std::{
+            sync::{
+                atomic::{AtomicUsize, Ordering},
+                Arc,
+            },
+            task::{Wake, Waker},
+        };
+
+        use futures::{
+            executor::block_on,
+            task::{noop_waker_ref, Context},
+            FutureExt, StreamExt,
+        };
+
+        use crate::queue::{channel, error::QueueError, prelude::Prio};
+
+
+        #[test]
+        fn receives_in_priority_order() {
+            let (tx, mut rx) = channel::channel();
+
+            block_on(async {
+                tx.send(Prio::wolock("first", Some(2))).await.unwrap();
+                tx.send(Prio::wolock("second", Some(0))).await.unwrap();
+                tx.send(Prio::wlock("third", Some(2))).await.unwrap();
+                assert_eq!(Some("second"), rx.recv().await);
+
+                drop(tx);
+                assert_eq!(vec!["third", "first"], rx.by_ref().collect::<Vec<_>>().await);
+                assert_eq!(None, rx.recv().await);
+            });
+        }
+
+        #[test]
+        fn bounded_send_waits_for_room() {
+            let (tx, mut rx) = channel::bounded(1);
+
+            assert!(tx.send(Prio::wolip(1u32)).now_or_never().is_some());
+            assert!(tx.send(Prio::wolip(2)).now_or_never().is_none());
+
+            let mut pending = tx.send(Prio::wolip(3));
+            assert!(pending.poll_unpin(&mut Context::from_waker(noop_waker_ref())).is_pending());
+
+            assert_eq!(Some(Some(1)), rx.recv().now_or_never());
+            assert!(block_on(pending).is_ok());
+            assert_eq!(Some(Some(3)), rx.recv().now_or_never());
+        }
+
+        #[test]
+        fn pending_send_keeps_one_waker() {
+            struct Count(AtomicUsize);
+
+            impl Wake for Count {
+                fn wake(self: Arc<Self>) {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+
+            let (tx, mut rx) = channel::bounded(1);
+            tx.send(Prio::wolip(1u32)).now_or_never().unwrap().unwrap();
+
+            let kept = Arc::new(Count(AtomicUsize::new(0)));
+            let dropped = Arc::new(Count(AtomicUsize::new(0)));
+            let mut pending = tx.send(Prio::wolip(2));
+            for _ in 0..3 {
+                assert!(pending.poll_unpin(&mut Context::from_waker(&Waker::from(Arc::clone(&kept)))).is_pending());
+            }
+            let mut abandoned = tx.send(Prio::wolip(3));
+            assert!(abandoned.poll_unpin(&mut Context::from_waker(&Waker::from(Arc::clone(&dropped)))).is_pending());
+            drop(abandoned);
+
+            assert_eq!(Some(Some(1)), rx.recv().now_or_never());
+            assert_eq!(1, kept.0.load(Ordering::SeqCst));
+            assert_eq!(0, dropped.0.load(Ordering::SeqCst));
+            assert!(block_on(pending).is_ok());
+        }
+
+        #[test]
+        fn send_after_receiver_dropped() {
+            let (tx, rx) = channel::channel();
+            drop(rx);
+
+            let err = block_on(tx.send(Prio::wolip("first"))).unwrap_err();
+            assert_eq!(QueueError::Closed(Prio::wolip("first")), err);
+        }
+    }
+
+    pub mod payloads {
+        use std::collections::HashMap;
+
+        use crate::prelude::{LockStatus, Prio, PriorityQueue, Queue, SizedQueue};
+
+
+        #[derive(Debug, PartialEq)]
+        struct Job {
+            name: String,
+        }
+
+        fn job(name: &str) -> Job {
+            Job { name: name.to_string() }
+        }
+
+        #[test]
+        fn non_clone_payloads() {
+            let mut queue = Queue::new();
+            queue.push(Prio::wolock(job("second"), Some(1))).unwrap();
+            let first = queue.push(Prio::new(job("first"), Some(0), LockStatus::Locked)).unwrap();
+
+            assert_eq!(Some((&job("first"), first)), queue.peek());
+            for (_, job) in queue.iter_mut() {
+                job.name.push('!');
+            }
+            assert_eq!(Some(job("first!")), queue.pop());
+            assert_eq!(Some(job("second!")), queue.pop());
+        }
+
+        #[test]
+        fn non_clone_through_traits() {
+            fn take<Q: PriorityQueue<Job>>(queue: &mut Q) -> Option<Job> {
+                queue.push(Prio::wolip(job("queued"))).unwrap();
+                queue.pop()
+            }
+
+            assert_eq!(Some(job("queued")), take(&mut Queue::new()));
+            assert_eq!(Some(job("queued")), take(&mut SizedQueue::new(1)));
+        }
+
+        #[test]
+        fn mixed_type_parameters() {
+            let mut results: Queue<Result<u8, String>> = Queue::new();
+            results.push(Prio::wolock(Err("failed".to_string()), Some(1))).unwrap();
+            results.push(Prio::wolock(Ok(1), Some(0))).unwrap();
+            assert_eq!(vec![Ok(1), Err("failed".to_string())], results.get_elements());
+
+            let mut maps: Queue<HashMap<String, u8>> = Queue::new();
+            maps.push(Prio::wolip(HashMap::from([("one".to_string(), 1)]))).unwrap();
+            assert_eq!(Some(1), maps.pop().unwrap().get("one").copied());
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
     pub mod queue_tuples {
         use crate::queue::{Queue, prelude::Prio};
 
@@ -154,8 +1769,8 @@ pub mod tests {
         fn test_tuple_of_2() {
             let mut queue: Queue<(u8, String)> = Queue::new();
 
-            queue.push(Prio::wolock((1, "first".to_string()), Some(0)));
-            queue.push(Prio::wolock((2, "second".to_string()), Some(0)));
+            queue.push(Prio::wolock((1, "first".to_string()), Some(0))).unwrap();
+            queue.push(Prio::wolock((2, "second".to_string()), Some(0))).unwrap();
 
             let elements = queue.get_elements();
             assert_eq!(
@@ -168,8 +1783,8 @@ pub mod tests {
         fn test_tuple_of_6() {
             let mut queue: Queue<(u8, u8, u8, u8, u8, u8)> = Queue::new();
 
-            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6), Some(0)));
-            queue.push(Prio::wolock((7, 8, 9, 10, 11, 12), Some(0)));
+            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6), Some(0))).unwrap();
+            queue.push(Prio::wolock((7, 8, 9, 10, 11, 12), Some(0))).unwrap();
 
             let elements = queue.get_elements();
             assert_eq!(elements, vec![(1, 2, 3, 4, 5, 6), (7, 8, 9, 10, 11, 12)]);
@@ -179,8 +1794,8 @@ pub mod tests {
         fn test_tuple_of_8() {
             let mut queue: Queue<(u8, u8, u8, u8, u8, u8, u8, u8)> = Queue::new();
 
-            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6, 7, 8), Some(0)));
-            queue.push(Prio::wolock((9, 10, 11, 12, 13, 14, 15, 16), Some(0)));
+            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6, 7, 8), Some(0))).unwrap();
+            queue.push(Prio::wolock((9, 10, 11, 12, 13, 14, 15, 16), Some(0))).unwrap();
 
             let elements = queue.get_elements();
             assert_eq!(
@@ -193,8 +1808,8 @@ pub mod tests {
         fn test_tuple_of_12() {
             let mut queue: Queue<(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8)> = Queue::new();
 
-            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12), Some(0)));
-            queue.push(Prio::wolock((13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24), Some(0)));
+            queue.push(Prio::wolock((1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12), Some(0))).unwrap();
+            queue.push(Prio::wolock((13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24), Some(0))).unwrap();
 
             let elements = queue.get_elements();
             assert_eq!(
@@ -229,8 +1844,8 @@ pub mod tests {
             list2.push_back(6);
 
             // Push both linked lists into the queue
-            queue.push(Prio::wolock(list1.clone(), Some(0)));
-            queue.push(Prio::wolock(list2.clone(), Some(0)));
+            queue.push(Prio::wolock(list1.clone(), Some(0))).unwrap();
+            queue.push(Prio::wolock(list2.clone(), Some(0))).unwrap();
 
             // Get the elements from the queue
             let elements = queue.get_elements();
@@ -272,8 +1887,8 @@ pub mod tests {
             map2.insert(6, 60);
 
             // Push both BTreeMaps into the queue
-            queue.push(Prio::wolock(map1.clone(), Some(0)));
-            queue.push(Prio::wolock(map2.clone(), Some(0)));
+            queue.push(Prio::wolock(map1.clone(), Some(0))).unwrap();
+            queue.push(Prio::wolock(map2.clone(), Some(0))).unwrap();
 
             // Get the elements from the queue
             let elements = queue.get_elements();
@@ -0,0 +1,48 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::prelude::QueueId;
+
+
+/// When each unlocked element next moves up a level under the aging policy, as measured by the queue's clock.
+pub(crate) struct Ages {
+    at: HashMap<QueueId, u64>,
+    order: BTreeSet<(u64, QueueId)>,
+}
+
+impl Ages {
+    pub(crate) fn new() -> Self {
+        Self {
+            at: HashMap::new(),
+            order: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: QueueId, at: u64) {
+        if let Some(old) = self.at.insert(id, at) {
+            self.order.remove(&(old, id));
+        }
+        self.order.insert((at, id));
+    }
+
+    pub(crate) fn remove(&mut self, id: QueueId) {
+        if let Some(at) = self.at.remove(&id) {
+            self.order.remove(&(at, id));
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.at.clear();
+        self.order.clear();
+    }
+
+    /// Forgets and returns the earliest element due to move up a level at `now`, with when it was due.
+    pub(crate) fn pop_due(&mut self, now: u64) -> Option<(u64, QueueId)> {
+        let (at, id) = *self.order.first()?;
+        if at > now {
+            return None;
+        }
+        self.order.pop_first();
+        self.at.remove(&id);
+        Some((at, id))
+    }
+}
@@ -0,0 +1,314 @@
+//! An async priority channel.
+//!
+//! `channel` creates an unbounded channel backed by a `Queue`, and `bounded` creates one backed by a
+//! `SizedQueue`, where `send` waits for space instead of failing. Either way the `Receiver` gets items
+//! in the same Priority, Lock, Time order as the queue would pop them.
+//!
+//! The channel closes once every `Sender` is dropped and the remaining items have been received.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use futures_core::Stream;
+
+use super::{
+    error::QueueError,
+    prelude::{Prio, QueueId, Queueable},
+    sized::SizedQueue,
+    Queue,
+};
+
+
+/// Creates an unbounded priority channel.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>)
+where
+    T: Queueable,
+{
+    open(Buffer::Unbounded(Queue::new()))
+}
+
+/// Creates a priority channel that holds at most `size` items.
+/// Senders wait for the receiver to make room once it is full.
+pub fn bounded<T>(size: u32) -> (Sender<T>, Receiver<T>)
+where
+    T: Queueable,
+{
+    open(Buffer::Bounded(SizedQueue::new(size)))
+}
+
+fn open<T>(buffer: Buffer<T>) -> (Sender<T>, Receiver<T>)
+where
+    T: Queueable,
+{
+    let chan = Arc::new(Mutex::new(Chan {
+        buffer,
+        senders: 1,
+        receiver_alive: true,
+        recv_waker: None,
+        send_wakers: HashMap::new(),
+        next_waiter: 0,
+    }));
+
+    (
+        Sender {
+            chan: Arc::clone(&chan),
+        },
+        Receiver { chan },
+    )
+}
+
+enum Buffer<T>
+where
+    T: Queueable,
+{
+    Unbounded(Queue<T>),
+    Bounded(SizedQueue<T>),
+}
+
+impl<T> Buffer<T>
+where
+    T: Queueable,
+{
+    fn push(&mut self, item: Prio<T>) -> Result<QueueId, QueueError<T>> {
+        match self {
+            Buffer::Unbounded(queue) => queue.push(item),
+            Buffer::Bounded(queue) => queue.push(item),
+        }
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        match self {
+            Buffer::Unbounded(queue) => queue.pop(),
+            Buffer::Bounded(queue) => queue.pop(),
+        }
+    }
+}
+
+struct Chan<T>
+where
+    T: Queueable,
+{
+    buffer: Buffer<T>,
+    senders: usize,
+    receiver_alive: bool,
+    recv_waker: Option<Waker>,
+    /// The waker of each pending `Send`, by the slot it was given.
+    send_wakers: HashMap<u64, Waker>,
+    next_waiter: u64,
+}
+
+impl<T> Chan<T>
+where
+    T: Queueable,
+{
+    fn wake_senders(&mut self) {
+        for (_, waker) in self.send_wakers.drain() {
+            waker.wake();
+        }
+    }
+}
+
+fn lock<T>(chan: &Mutex<Chan<T>>) -> MutexGuard<'_, Chan<T>>
+where
+    T: Queueable,
+{
+    chan.lock().expect("channel lock poisoned")
+}
+
+/// The sending half of a priority channel. It can be cloned to send from many tasks.
+pub struct Sender<T>
+where
+    T: Queueable,
+{
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Sender<T>
+where
+    T: Queueable,
+{
+    /// Sends a prioritized item, waiting for room if the channel is bounded and full.
+    /// Returns the identifier the item was given in the underlying queue.
+    ///
+    /// Fails with `QueueError::Closed` if the receiver has been dropped.
+    pub fn send(&self, item: Prio<T>) -> Send<'_, T> {
+        Send {
+            sender: self,
+            item: Some(item),
+            waiter: None,
+        }
+    }
+}
+
+impl<T> Clone for Sender<T>
+where
+    T: Queueable,
+{
+    fn clone(&self) -> Self {
+        lock(&self.chan).senders += 1;
+        Self {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T>
+where
+    T: Queueable,
+{
+    fn drop(&mut self) {
+        let mut chan = lock(&self.chan);
+        chan.senders -= 1;
+        if chan.senders == 0 {
+            if let Some(waker) = chan.recv_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Future returned by `Sender::send`.
+pub struct Send<'a, T>
+where
+    T: Queueable,
+{
+    sender: &'a Sender<T>,
+    item: Option<Prio<T>>,
+    /// The slot this send keeps its waker in while the channel is full.
+    waiter: Option<u64>,
+}
+
+impl<T> Unpin for Send<'_, T> where T: Queueable {}
+
+impl<T> Future for Send<'_, T>
+where
+    T: Queueable,
+{
+    type Output = Result<QueueId, QueueError<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let item = this.item.take().expect("polled after completion");
+        let mut chan = lock(&this.sender.chan);
+
+        if let Some(waiter) = this.waiter {
+            chan.send_wakers.remove(&waiter);
+        }
+        if !chan.receiver_alive {
+            return Poll::Ready(Err(QueueError::Closed(item)));
+        }
+
+        match chan.buffer.push(item) {
+            Ok(id) => {
+                if let Some(waker) = chan.recv_waker.take() {
+                    waker.wake();
+                }
+                Poll::Ready(Ok(id))
+            }
+            Err(QueueError::Full(item)) => {
+                this.item = Some(item);
+                let waiter = *this.waiter.get_or_insert_with(|| {
+                    chan.next_waiter += 1;
+                    chan.next_waiter
+                });
+                chan.send_wakers.insert(waiter, cx.waker().clone());
+                Poll::Pending
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
+
+impl<T> Drop for Send<'_, T>
+where
+    T: Queueable,
+{
+    fn drop(&mut self) {
+        if let Some(waiter) = self.waiter {
+            lock(&self.sender.chan).send_wakers.remove(&waiter);
+        }
+    }
+}
+
+/// The receiving half of a priority channel.
+///
+/// Besides `recv`, the receiver is a `Stream` of items in priority order.
+pub struct Receiver<T>
+where
+    T: Queueable,
+{
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Receiver<T>
+where
+    T: Queueable,
+{
+    /// Receives the next item in the channel, waiting for one to be sent.
+    /// Returns `None` once every sender is dropped and the channel is empty.
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv { receiver: self }
+    }
+
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut chan = lock(&self.chan);
+
+        if let Some(item) = chan.buffer.pop() {
+            chan.wake_senders();
+            return Poll::Ready(Some(item));
+        }
+
+        if chan.senders == 0 {
+            return Poll::Ready(None);
+        }
+
+        chan.recv_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Stream for Receiver<T>
+where
+    T: Queueable,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Receiver<T>
+where
+    T: Queueable,
+{
+    fn drop(&mut self) {
+        let mut chan = lock(&self.chan);
+        chan.receiver_alive = false;
+        chan.wake_senders();
+    }
+}
+
+/// Future returned by `Receiver::recv`.
+pub struct Recv<'a, T>
+where
+    T: Queueable,
+{
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for Recv<'_, T>
+where
+    T: Queueable,
+{
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,70 @@
+//! Where queues get the timestamps that order elements of equal priority.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+
+/// A source of timestamps, in nanoseconds since the Unix epoch.
+///
+/// Timestamps must never go backwards, or elements pushed later may come out first.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// The wall-clock time when the process first asked for a timestamp, and the instant it did.
+static ANCHOR: LazyLock<(u64, Instant)> = LazyLock::new(|| {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+    (since_epoch.as_nanos() as u64, Instant::now())
+});
+
+/// The clock queues use by default.
+///
+/// It reads the wall clock once, then counts forward from there on the monotonic clock,
+/// so changes to the system time can't reorder elements.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> u64 {
+        let (anchor, started) = *ANCHOR;
+        anchor + started.elapsed().as_nanos() as u64
+    }
+}
+
+/// A clock that only moves when told to, for making timestamp order deterministic in tests.
+///
+/// Share it with a queue through an `Arc`, and keep a handle to move it.
+#[derive(Default, Debug)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+impl ManualClock {
+    /// Creates a clock reading `nanos`.
+    pub fn new(nanos: u64) -> Self {
+        Self {
+            nanos: AtomicU64::new(nanos),
+        }
+    }
+
+    /// Sets the time to `nanos`.
+    pub fn set(&self, nanos: u64) {
+        self.nanos.store(nanos, Ordering::SeqCst);
+    }
+
+    /// Moves the time forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.nanos.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,413 @@
+//! A `Queue` that survives crashes by logging every change to disk.
+//!
+//! A `DurableQueue` keeps its state in a directory holding two files: `snapshot.json`, a full copy of
+//! the queue as of the last compaction, and `wal.log`, an append-only log of every change since then,
+//! one JSON record per line. Opening the directory loads the snapshot and replays the log on top.
+//!
+//! Each change is appended to the log before it is applied in memory. A record cut short by a crash
+//! at the end of the log is ignored on replay, and cut off the log before anything new is appended.
+
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::{Instant, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    error::QueueError,
+    prelude::{Deadline, FifoPosition, LockStatus, Prio, QueueFlag, QueueId, Queueable},
+    Queue,
+};
+
+
+const SNAPSHOT: &str = "snapshot.json";
+const LOG: &str = "wal.log";
+
+/// When a `DurableQueue` asks the operating system to flush its log to the disk.
+///
+/// Records are always handed to the operating system as soon as they are written,
+/// so they survive the process crashing. Syncing also makes them survive power loss.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SyncPolicy {
+    /// Sync after every change.
+    Always,
+    /// Sync once this many changes have been written since the last sync.
+    Batched(usize),
+}
+
+/// Errors returned by `DurableQueue` operations.
+#[derive(Debug)]
+pub enum DurableError<T>
+where
+    T: Queueable,
+{
+    /// The operation was rejected by the queue.
+    Queue(QueueError<T>),
+    /// Reading or writing the queue's files failed.
+    Io(io::Error),
+}
+
+impl<T> fmt::Display for DurableError<T>
+where
+    T: Queueable,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurableError::Queue(err) => err.fmt(f),
+            DurableError::Io(err) => write!(f, "durable queue I/O failed: {}", err),
+        }
+    }
+}
+
+impl<T> Error for DurableError<T>
+where
+    T: Queueable + fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DurableError::Queue(_) => None,
+            DurableError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl<T> From<QueueError<T>> for DurableError<T>
+where
+    T: Queueable,
+{
+    fn from(err: QueueError<T>) -> Self {
+        DurableError::Queue(err)
+    }
+}
+
+impl<T> From<io::Error> for DurableError<T>
+where
+    T: Queueable,
+{
+    fn from(err: io::Error) -> Self {
+        DurableError::Io(err)
+    }
+}
+
+/// One change to the queue, as written to the log.
+#[derive(Serialize, Deserialize)]
+enum Record<T> {
+    Push {
+        flag: QueueFlag,
+        item: T,
+        /// When the element expires, if it was pushed with a time-to-live.
+        #[serde(default)]
+        expires: Option<SystemTime>,
+    },
+    Remove { id: QueueId },
+    Update { flag: QueueFlag },
+}
+
+/// The contents of the snapshot file.
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile<Q> {
+    queue: Q,
+    /// When each element with a time-to-live expires, in wall-clock time like the log.
+    expires: Vec<(QueueId, SystemTime)>,
+}
+
+/// A DurableQueue is a `Queue` backed by a write-ahead log on local disk.
+///
+/// Pushes, pops and changes to priority or lock status are written to the log before they are applied,
+/// so reopening the same directory after a crash gives back the queue as it was.
+/// The log grows with every change until `compact` folds it into a snapshot.
+pub struct DurableQueue<T>
+where
+    T: Queueable,
+{
+    queue: Queue<T>,
+    dir: PathBuf,
+    log: BufWriter<File>,
+    sync_policy: SyncPolicy,
+    /// Records written since the log was last synced.
+    unsynced: usize,
+    /// Records written since the log was last compacted.
+    logged: usize,
+    compact_after: Option<usize>,
+}
+
+impl<T> DurableQueue<T>
+where
+    T: Queueable + Serialize + DeserializeOwned,
+{
+    /// Opens the queue stored in `dir`, creating the directory if needed.
+    ///
+    /// The log is synced after every change until `set_sync_policy` says otherwise.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, DurableError<T>> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut queue = match File::open(dir.join(SNAPSHOT)) {
+            Ok(file) => {
+                let snapshot: SnapshotFile<Queue<T>> = serde_json::from_reader(BufReader::new(file)).map_err(io::Error::from)?;
+                let mut queue = snapshot.queue;
+                for (id, expires) in snapshot.expires {
+                    queue.expiry.insert(id, expires.instant());
+                }
+                queue
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Queue::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let (logged, valid) = replay(&mut queue, &dir.join(LOG))?;
+
+        let log = OpenOptions::new().create(true).append(true).open(dir.join(LOG))?;
+        if log.metadata()?.len() > valid {
+            log.set_len(valid)?;
+            log.sync_all()?;
+        }
+        Ok(Self {
+            queue,
+            dir,
+            log: BufWriter::new(log),
+            sync_policy: SyncPolicy::Always,
+            unsynced: 0,
+            logged,
+            compact_after: None,
+        })
+    }
+
+    /// Sets when the log is synced to disk.
+    pub fn set_sync_policy(&mut self, policy: SyncPolicy) {
+        self.sync_policy = policy;
+    }
+
+    /// Compacts the log automatically once it holds `records` changes, or never with `None`.
+    pub fn set_compaction(&mut self, records: Option<usize>) {
+        self.compact_after = records;
+    }
+
+    /// Pushes a prioritized item to the queue.
+    /// Returns a unique identifier that can be used to track and retrieve this element in the queue
+    ///
+    /// A time-to-live is stored as a point in wall-clock time, in the log and in snapshots,
+    /// so it keeps running while the queue is closed.
+    pub fn push(&mut self, item: Prio<T>) -> Result<QueueId, DurableError<T>> {
+        let Some(priority) = item.priority() else {
+            return Err(QueueError::MissingPriority(item).into());
+        };
+        let Some(id) = self.queue.next_identifier() else {
+            return Err(QueueError::IdsExhausted(item).into());
+        };
+
+        let flag = self.queue.new_flag(priority, item.locked, id);
+        let expires = item.ttl().map(|ttl| SystemTime::now() + ttl);
+        self.append(&Record::Push { flag, item: &item.item, expires })?;
+        self.queue.track_ttl(id, item.ttl());
+        self.queue.insert(flag, item.item);
+        self.maybe_compact()?;
+
+        Ok(flag.identifier)
+    }
+
+    /// Pops the next item in the queue.
+    pub fn pop(&mut self) -> Result<Option<T>, DurableError<T>> {
+        match self.queue.peek() {
+            Some((_, id)) => self.pop_by_id(id),
+            None => Ok(None),
+        }
+    }
+
+    /// Pops an element based on the id
+    pub fn pop_by_id(&mut self, id: QueueId) -> Result<Option<T>, DurableError<T>> {
+        if !self.queue.index.contains_key(&id) {
+            return Ok(None);
+        }
+
+        self.append(&Record::<&T>::Remove { id })?;
+        let item = self.queue.pop_by_id(id);
+        self.maybe_compact()?;
+
+        Ok(item)
+    }
+
+    /// Changes the priority of an element, keeping its identifier.
+    pub fn reprioritize(&mut self, id: QueueId, priority: u32, position: FifoPosition) -> Result<(), DurableError<T>> {
+        self.update(id, position, |flag| flag.priority = priority)
+    }
+
+    /// Locks an element in place, keeping its identifier and position.
+    pub fn lock(&mut self, id: QueueId) -> Result<(), DurableError<T>> {
+        self.update(id, FifoPosition::Keep, |flag| flag.locked = LockStatus::Locked)
+    }
+
+    /// Unlocks an element, keeping its identifier and position.
+    pub fn unlock(&mut self, id: QueueId) -> Result<(), DurableError<T>> {
+        self.update(id, FifoPosition::Keep, |flag| flag.locked = LockStatus::Unlocked)
+    }
+
+    /// Peeks the next item in the queue.
+    /// Returns a reference to the next item in the queue.
+    pub fn peek(&self) -> Option<(&T, QueueId)> {
+        self.queue.peek()
+    }
+
+    /// Returns the size of the queue
+    pub fn size(&self) -> usize {
+        self.queue.size()
+    }
+
+    /// Writes the whole queue to a new snapshot and empties the log.
+    pub fn compact(&mut self) -> Result<(), DurableError<T>> {
+        let tmp = self.dir.join(format!("{}.tmp", SNAPSHOT));
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        let snapshot = SnapshotFile {
+            queue: &self.queue,
+            expires: self.queue.expiry.iter().map(|(id, at)| (id, wall_clock(at))).collect(),
+        };
+        serde_json::to_writer(&mut file, &snapshot).map_err(io::Error::from)?;
+        file.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT))?;
+
+        // Replay skips pushes the snapshot already holds, so a crash before the log is emptied loses nothing.
+        let log = File::create(self.dir.join(LOG))?;
+        log.sync_all()?;
+        self.log = BufWriter::new(log);
+        self.unsynced = 0;
+        self.logged = 0;
+        Ok(())
+    }
+
+    /// Syncs every change written so far to disk, whatever the sync policy.
+    pub fn sync(&mut self) -> Result<(), DurableError<T>> {
+        self.log.flush()?;
+        self.log.get_ref().sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    fn update(&mut self, id: QueueId, position: FifoPosition, change: impl FnOnce(&mut QueueFlag)) -> Result<(), DurableError<T>> {
+        let mut flag = *self.queue.index.get(&id).ok_or(QueueError::NotFound(id))?;
+        change(&mut flag);
+        if position == FifoPosition::Reset {
+            flag.timestamp = self.queue.clock.now();
+        }
+
+        self.append(&Record::<&T>::Update { flag })?;
+        let item = self.queue.remove(id).expect("index out of sync with entries");
+        self.queue.insert(flag, item);
+        self.maybe_compact()
+    }
+
+    fn append(&mut self, record: &Record<&T>) -> Result<(), DurableError<T>> {
+        serde_json::to_writer(&mut self.log, record).map_err(io::Error::from)?;
+        self.log.write_all(b"\n")?;
+        self.log.flush()?;
+        self.unsynced += 1;
+        self.logged += 1;
+
+        match self.sync_policy {
+            SyncPolicy::Batched(records) if self.unsynced < records => Ok(()),
+            _ => self.sync(),
+        }
+    }
+
+    fn maybe_compact(&mut self) -> Result<(), DurableError<T>> {
+        match self.compact_after {
+            Some(records) if self.logged >= records => self.compact(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T> DurableQueue<T>
+where
+    T: Queueable + Clone + Serialize + DeserializeOwned,
+{
+    /// Returns a copy of the element with the given identifier.
+    pub fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T>> {
+        self.queue.retrieve_by_id(id)
+    }
+
+    /// Returns all elements in the queue as a Vec.
+    pub fn get_elements(&self) -> Vec<T> {
+        self.queue.get_elements()
+    }
+}
+
+/// Applies every record in the log at `path` to `queue`.
+/// Returns how many records there were, and the length of the log up to the end of the last whole one.
+fn replay<T>(queue: &mut Queue<T>, path: &Path) -> io::Result<(usize, u64)>
+where
+    T: Queueable + DeserializeOwned,
+{
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(err) => return Err(err),
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let mut replayed = 0;
+    let mut valid = 0;
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        // Only the last record can lack its newline, when a crash cut it short.
+        if !line.ends_with(b"\n") {
+            break;
+        }
+        let record: Record<T> = serde_json::from_slice(&line)?;
+
+        match record {
+            Record::Push { flag, item, expires } => {
+                queue.origin = flag.identifier.origin;
+                queue.next_id.fetch_max(flag.identifier.seq.saturating_add(1), Ordering::Relaxed);
+                // A crash during `compact` can leave pushes the snapshot already holds in the log.
+                if !queue.index.contains_key(&flag.identifier) {
+                    if let Some(expires) = expires {
+                        queue.expiry.insert(flag.identifier, expires.instant());
+                    }
+                    queue.insert(flag, item);
+                }
+            }
+            Record::Remove { id } => {
+                queue.pop_by_id(id);
+            }
+            Record::Update { flag } => {
+                if let Some(item) = queue.remove(flag.identifier) {
+                    queue.insert(flag, item);
+                }
+            }
+        }
+        replayed += 1;
+        valid += read as u64;
+    }
+    Ok((replayed, valid))
+}
+
+/// The wall-clock time of `at`.
+fn wall_clock(at: Instant) -> SystemTime {
+    let now = Instant::now();
+    match at.checked_duration_since(now) {
+        Some(ahead) => SystemTime::now() + ahead,
+        None => SystemTime::now() - now.duration_since(at),
+    }
+}
+
+impl<T> Drop for DurableQueue<T>
+where
+    T: Queueable,
+{
+    fn drop(&mut self) {
+        if self.log.flush().is_ok() {
+            let _ = self.log.get_ref().sync_data();
+        }
+    }
+}
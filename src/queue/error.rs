@@ -0,0 +1,94 @@
+use std::{error::Error, fmt};
+
+use super::prelude::{AdmissionBand, Prio, QueueId, Queueable};
+
+
+/// Errors returned by `Queue` and `SizedQueue` operations.
+///
+/// Variants that reject an element hand the `Prio` back, so the caller can retry it.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum QueueError<T, P = u32>
+where
+    T: Queueable,
+{
+    /// The queue holds no elements.
+    Empty,
+    /// No element with this identifier is in the queue.
+    NotFound(QueueId),
+    /// The queue is at its maximum size.
+    Full(Prio<T, P>),
+    /// The element was pushed without a priority.
+    MissingPriority(Prio<T, P>),
+    /// The lease on this element ran out or was already settled.
+    LeaseExpired(QueueId),
+    /// The receiving half of a channel has been dropped.
+    Closed(Prio<T, P>),
+    /// The queue holds this many elements, more than the requested maximum size.
+    Overfull(usize),
+    /// The requested maximum size does not fit in a `u32`.
+    CapacityOverflow,
+    /// The element's priority band has taken up all the room it is allowed in the queue.
+    BandFull(AdmissionBand<P>, Prio<T, P>),
+    /// The queue has handed out every identifier it can.
+    IdsExhausted(Prio<T, P>),
+    /// The identifier was handed out by a different queue.
+    ForeignId(QueueId),
+    /// The queue is at its maximum size, so the element with this identifier stays where it was.
+    NoRoom(QueueId),
+}
+
+impl<T, P> QueueError<T, P>
+where
+    T: Queueable,
+{
+    /// Returns the rejected element, if this error carries one.
+    pub fn into_item(self) -> Option<Prio<T, P>> {
+        match self {
+            QueueError::Full(item)
+            | QueueError::MissingPriority(item)
+            | QueueError::Closed(item)
+            | QueueError::BandFull(_, item)
+            | QueueError::IdsExhausted(item) => Some(item),
+            QueueError::Empty
+            | QueueError::NotFound(_)
+            | QueueError::LeaseExpired(_)
+            | QueueError::Overfull(_)
+            | QueueError::CapacityOverflow
+            | QueueError::ForeignId(_)
+            | QueueError::NoRoom(_) => None,
+        }
+    }
+}
+
+impl<T, P> fmt::Display for QueueError<T, P>
+where
+    T: Queueable,
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::Empty => write!(f, "queue is empty"),
+            QueueError::NotFound(id) => write!(f, "ID {} not found in the queue", id),
+            QueueError::Full(_) => write!(f, "queue is full"),
+            QueueError::MissingPriority(_) => write!(f, "priority must be set"),
+            QueueError::LeaseExpired(id) => write!(f, "lease on ID {} has expired", id),
+            QueueError::Closed(_) => write!(f, "channel is closed"),
+            QueueError::Overfull(size) => write!(f, "queue holds {} elements, more than the new maximum size", size),
+            QueueError::CapacityOverflow => write!(f, "maximum size does not fit in a u32"),
+            QueueError::BandFull(band, _) => {
+                write!(f, "priorities {:?} and up are limited to {}% of the queue", band.priority, band.percent)
+            }
+            QueueError::IdsExhausted(_) => write!(f, "queue has run out of identifiers"),
+            QueueError::ForeignId(id) => write!(f, "ID {} belongs to another queue", id),
+            QueueError::NoRoom(id) => write!(f, "queue is full, ID {} was not moved", id),
+        }
+    }
+}
+
+impl<T, P> Error for QueueError<T, P>
+where
+    T: Queueable + fmt::Debug,
+    P: fmt::Debug,
+{
+}
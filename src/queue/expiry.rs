@@ -0,0 +1,69 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Instant,
+};
+
+use super::prelude::QueueId;
+
+
+/// When each element with a time-to-live stops being worth popping.
+pub(crate) struct Expiry {
+    at: HashMap<QueueId, Instant>,
+    order: BTreeSet<(Instant, QueueId)>,
+}
+
+impl Expiry {
+    pub(crate) fn new() -> Self {
+        Self {
+            at: HashMap::new(),
+            order: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, id: QueueId, at: Instant) {
+        if let Some(old) = self.at.insert(id, at) {
+            self.order.remove(&(old, id));
+        }
+        self.order.insert((at, id));
+    }
+
+    /// Forgets the element, returning whether it had already expired at `now`.
+    pub(crate) fn remove(&mut self, id: QueueId, now: Instant) -> bool {
+        match self.at.remove(&id) {
+            Some(at) => {
+                self.order.remove(&(at, id));
+                at <= now
+            }
+            None => false,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (QueueId, Instant)> + '_ {
+        self.at.iter().map(|(id, at)| (*id, *at))
+    }
+
+    pub(crate) fn is_expired(&self, id: QueueId, now: Instant) -> bool {
+        self.at.get(&id).is_some_and(|at| *at <= now)
+    }
+
+    pub(crate) fn count_expired(&self, now: Instant) -> usize {
+        self.order.range(..=(now, QueueId::MAX)).count()
+    }
+
+    /// The identifiers of the elements expired at `now`.
+    pub(crate) fn expired(&self, now: Instant) -> impl Iterator<Item = QueueId> + '_ {
+        self.order.range(..=(now, QueueId::MAX)).map(|(_, id)| *id)
+    }
+
+    /// Forgets and returns the identifier of the earliest element expired at `now`.
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<QueueId> {
+        let (at, id) = *self.order.first()?;
+        if at > now {
+            return None;
+        }
+        self.order.pop_first();
+        self.at.remove(&id);
+        Some(id)
+    }
+}
@@ -0,0 +1,268 @@
+//! Iterators over the elements of `Queue` and `SizedQueue`.
+
+use std::{
+    collections::btree_map,
+    iter::FusedIterator,
+    time::Instant,
+    vec,
+};
+
+use super::{
+    error::QueueError,
+    expiry::Expiry,
+    prelude::{Prio, QueueFlag, Queueable},
+    Queue,
+};
+
+
+/// Borrows the elements of a queue in the order they would be popped, see `Queue::iter`.
+///
+/// The elements waiting in the queue are already in order, so the iterator only merges them
+/// with the delayed elements that have come due, skipping expired ones as it goes.
+pub struct Iter<'a, T, P = u32> {
+    entries: Side<btree_map::Iter<'a, QueueFlag<P>, T>>,
+    due: Side<vec::IntoIter<(&'a QueueFlag<P>, &'a T)>>,
+    expiry: &'a Expiry,
+    now: Instant,
+}
+
+impl<'a, T, P> Iter<'a, T, P>
+where
+    P: Ord,
+{
+    /// `due` holds the delayed elements that have come due, in the order they would be popped.
+    pub(super) fn new(
+        entries: btree_map::Iter<'a, QueueFlag<P>, T>,
+        due: Vec<(&'a QueueFlag<P>, &'a T)>,
+        expiry: &'a Expiry,
+        now: Instant,
+    ) -> Self {
+        Self {
+            entries: Side::new(entries),
+            due: Side::new(due.into_iter()),
+            expiry,
+            now,
+        }
+    }
+
+    fn is_expired(&self, (flag, _): &(&'a QueueFlag<P>, &'a T)) -> bool {
+        self.expiry.is_expired(flag.identifier, self.now)
+    }
+}
+
+/// One of the sorted sequences `Iter` merges, with the element it has looked at on each end.
+struct Side<I>
+where
+    I: Iterator,
+{
+    inner: I,
+    front: Option<I::Item>,
+    back: Option<I::Item>,
+}
+
+impl<I> Side<I>
+where
+    I: DoubleEndedIterator + ExactSizeIterator,
+{
+    fn new(inner: I) -> Self {
+        Self {
+            inner,
+            front: None,
+            back: None,
+        }
+    }
+
+    fn front(&mut self) -> Option<&I::Item> {
+        if self.front.is_none() {
+            self.front = self.inner.next().or_else(|| self.back.take());
+        }
+        self.front.as_ref()
+    }
+
+    fn back(&mut self) -> Option<&I::Item> {
+        if self.back.is_none() {
+            self.back = self.inner.next_back().or_else(|| self.front.take());
+        }
+        self.back.as_ref()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len() + self.front.is_some() as usize + self.back.is_some() as usize
+    }
+}
+
+impl<'a, T, P> Iterator for Iter<'a, T, P>
+where
+    P: Ord,
+{
+    type Item = (&'a QueueFlag<P>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match (self.entries.front(), self.due.front()) {
+                (None, None) => return None,
+                (Some(entry), Some(due)) if due.0 < entry.0 => self.due.front.take(),
+                (Some(_), _) => self.entries.front.take(),
+                (None, Some(_)) => self.due.front.take(),
+            }?;
+            if !self.is_expired(&next) {
+                return Some(next);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.entries.len() + self.due.len()))
+    }
+}
+
+impl<T, P> DoubleEndedIterator for Iter<'_, T, P>
+where
+    P: Ord,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match (self.entries.back(), self.due.back()) {
+                (None, None) => return None,
+                (Some(entry), Some(due)) if due.0 > entry.0 => self.due.back.take(),
+                (Some(_), _) => self.entries.back.take(),
+                (None, Some(_)) => self.due.back.take(),
+            }?;
+            if !self.is_expired(&next) {
+                return Some(next);
+            }
+        }
+    }
+}
+
+impl<T, P> FusedIterator for Iter<'_, T, P> where P: Ord {}
+
+/// Mutably borrows the elements of a queue in the order they would be popped, see `Queue::iter_mut`.
+pub struct IterMut<'a, T, P = u32> {
+    pub(super) inner: vec::IntoIter<(&'a QueueFlag<P>, &'a mut T)>,
+}
+
+/// The elements removed by `Queue::drain`, in the order they would have been popped.
+pub struct Drain<T, P = u32> {
+    pub(super) inner: vec::IntoIter<(QueueFlag<P>, T)>,
+}
+
+/// Every element of a queue, in the order they would be popped once due.
+pub struct IntoIter<T, P = u32> {
+    inner: btree_map::IntoIter<QueueFlag<P>, T>,
+}
+
+macro_rules! forward_iterator {
+    ($name:ident<$($lt:lifetime,)? T, P>, $item:ty) => {
+        impl<$($lt,)? T, P> Iterator for $name<$($lt,)? T, P> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.inner.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.inner.size_hint()
+            }
+        }
+
+        impl<$($lt,)? T, P> DoubleEndedIterator for $name<$($lt,)? T, P> {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.inner.next_back()
+            }
+        }
+
+        impl<$($lt,)? T, P> ExactSizeIterator for $name<$($lt,)? T, P> {}
+
+        impl<$($lt,)? T, P> FusedIterator for $name<$($lt,)? T, P> {}
+    };
+}
+
+forward_iterator!(IterMut<'a, T, P>, (&'a QueueFlag<P>, &'a mut T));
+forward_iterator!(Drain<T, P>, (QueueFlag<P>, T));
+forward_iterator!(IntoIter<T, P>, (QueueFlag<P>, T));
+
+impl<T, P> IntoIterator for Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (QueueFlag<P>, T);
+    type IntoIter = IntoIter<T, P>;
+
+    /// Delayed and reserved elements are included, in the place they will take once due.
+    /// Expired elements are left out.
+    fn into_iter(self) -> Self::IntoIter {
+        let now = Instant::now();
+        let expiry = self.expiry;
+        let mut entries = self.entries;
+        entries.extend(self.scheduled.into_values());
+        entries.retain(|flag, _| !expiry.is_expired(flag.identifier, now));
+
+        IntoIter {
+            inner: entries.into_iter(),
+        }
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (&'a QueueFlag<P>, &'a T);
+    type IntoIter = Iter<'a, T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a mut Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (&'a QueueFlag<P>, &'a mut T);
+    type IntoIter = IterMut<'a, T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Pushes every item in turn.
+///
+/// # Panics
+///
+/// Panics if an item has no priority set.
+impl<T, P> Extend<Prio<T, P>> for Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn extend<I: IntoIterator<Item = Prio<T, P>>>(&mut self, iter: I) {
+        for item in iter {
+            match self.push(item) {
+                Ok(_) => {}
+                Err(QueueError::MissingPriority(_)) => panic!("priority must be set"),
+                Err(_) => panic!("queue rejected the item"),
+            }
+        }
+    }
+}
+
+/// # Panics
+///
+/// Panics if an item has no priority set.
+impl<T, P> FromIterator<Prio<T, P>> for Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = Prio<T, P>>>(iter: I) -> Self {
+        let mut queue = Queue::default();
+        queue.extend(iter);
+        queue
+    }
+}
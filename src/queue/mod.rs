@@ -1,157 +1,907 @@
+mod aging;
+#[cfg(feature = "async")]
+pub mod channel;
+pub mod clock;
+#[cfg(feature = "durable")]
+pub mod durable;
+pub mod error;
+mod expiry;
+pub mod iter;
+pub mod peek;
 pub mod prelude;
+mod schedule;
+pub mod shared;
 pub mod sized;
+#[cfg(feature = "serde")]
+mod snapshot;
+#[cfg(feature = "spill")]
+pub mod spill;
+pub mod traits;
 
 use std::{
-    collections::BTreeMap,
-    sync::{atomic::AtomicU32, LazyLock, Mutex, RwLock},
+    cmp::Ordering as CmpOrdering,
+    collections::{BTreeMap, HashMap},
+    mem,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
-use prelude::{LockStatus, Prio, QueueFlag, Queueable};
+use aging::Ages;
+use clock::{Clock, MonotonicClock};
+use error::QueueError;
+use expiry::Expiry;
+use iter::{Drain, Iter, IterMut};
+use peek::PeekMut;
+use prelude::{AgingPolicy, AgingStep, Deadline, Direction, FifoPosition, Lease, LockStatus, OrderPolicy, Prio, QueueFlag, QueueId, Queueable};
+use schedule::Schedule;
+use traits::PriorityQueue;
 
 
 /// This queue holds data in order of a Priority, Lock, Time order.
 /// 
-/// Pushing to a queue, your element have the `queueable` and `clone` traits.
+/// Any type can be pushed to a queue. Elements only need to be `Clone` for the methods that hand out copies,
+/// such as `retrieve_by_id`, `get_elements`, `all` and `reserve`.
 /// Elements should be wrapped in a `Prio` struct, which has many helper methods for generating set priorities.
 /// Using prio you can also set your own lock status, and priority numbders.
-pub struct Queue<T>(BTreeMap<QueueFlag, T>, AtomicU32)
+///
+/// Priorities are `u32` by default, but can be any `Ord + Clone` type such as an enum of severity levels,
+/// a signed offset or a tuple. Queues of other priority types are created with `Queue::default()`.
+///
+/// Lowest priorities come out first, oldest first, unless the queue is created `with_order` another `OrderPolicy`.
+///
+/// Code that should accept any queue in the crate can take a `PriorityQueue` instead.
+pub struct Queue<T, P = u32>
 where
-    T: Queueable + Clone;
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// The elements, sorted by their flags.
+    entries: BTreeMap<QueueFlag<P>, T>,
+    /// Maps each identifier in the queue to its flag, so lookups by id don't scan `entries`.
+    index: HashMap<QueueId, QueueFlag<P>>,
+    /// Elements pushed with a delay or reserved by a consumer, held aside until they are due.
+    scheduled: Schedule<T, P>,
+    /// How many elements, due or not, are held at each priority.
+    priorities: BTreeMap<P, usize>,
+    /// The lease token of every reserved element.
+    leases: HashMap<QueueId, u64>,
+    next_lease: u64,
+    /// How many times each element has been reported as failed.
+    failures: HashMap<QueueId, u32>,
+    /// The failure limit and the queue elements move to once they reach it.
+    dead_letter: Option<(u32, Box<Queue<T, P>>)>,
+    /// When elements pushed with a time-to-live expire.
+    expiry: Expiry,
+    /// Drawn at random for each queue, and stamped on every identifier it hands out.
+    origin: u64,
+    /// The sequence number of the next identifier.
+    next_id: AtomicU64,
+    order: OrderPolicy,
+    /// Where timestamps come from.
+    clock: Arc<dyn Clock>,
+    /// The aging policy, and how it moves a priority up by a number of levels.
+    aging: Option<(AgingPolicy, AgingStep<P>)>,
+    /// When each unlocked element in `entries` next moves up a level, while aging is on.
+    ages: Ages,
+}
 
 impl<T> Queue<T>
 where
-    T: Queueable + Clone,
+    T: Queueable,
 {
     /// Creates a new LockableQueue.
     pub fn new() -> Self {
-        Self(BTreeMap::new(), AtomicU32::new(0))
+        Self::default()
+    }
+
+    /// Sets the aging policy, or turns aging off with `None`.
+    ///
+    /// Elements only age for the time they wait after the policy is set.
+    pub fn set_aging(&mut self, policy: Option<AgingPolicy>) {
+        let step = match self.order.direction {
+            Direction::MinFirst => u32::saturating_sub,
+            Direction::MaxFirst => u32::saturating_add,
+        };
+        self.set_aging_with(policy, step)
+    }
+
+    /// Generates a new thread-safe LockableQueue
+    #[deprecated(note = "use `SharedQueue`, which can block until an item is available")]
+    pub const fn thread_safe_new() -> LazyLock<RwLock<Mutex<Queue<T>>>> {
+        LazyLock::new(|| RwLock::new(Mutex::new(Queue::<T>::new()))) 
+    }
+}
+
+impl<T, P> Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// Creates a new queue that orders its elements by `order`.
+    pub fn with_order(order: OrderPolicy) -> Self {
+        Self {
+            order,
+            ..Self::default()
+        }
+    }
+
+    /// Returns how the queue orders its elements.
+    pub fn order(&self) -> OrderPolicy {
+        self.order
+    }
+
+    /// Returns whether `id` was handed out by this queue, whether or not its element is still in it.
+    ///
+    /// Identifiers of elements moved to the dead-letter queue belong to both.
+    ///
+    /// Methods returning a `Result` reject foreign identifiers with `QueueError::ForeignId`.
+    /// `pop_by_id`, `drop_dead_letter` and `failures` treat them like any identifier that isn't in the queue,
+    /// so callers that need to tell the two apart should check `owns` first.
+    pub fn owns(&self, id: QueueId) -> bool {
+        id.origin == self.origin
+    }
+
+    /// Makes the queue take its timestamps from `clock`, which is a `MonotonicClock` by default.
+    ///
+    /// Meant to be called right after creating the queue: elements already in it keep their timestamps.
+    /// Delayed and reserved elements still come due by the system's monotonic clock, see `Queue::push_at`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the aging policy, or turns aging off with `None`.
+    /// `step` moves a priority up by the given number of levels.
+    ///
+    /// Elements only age for the time they wait after the policy is set.
+    pub fn set_aging_with(&mut self, policy: Option<AgingPolicy>, step: AgingStep<P>) {
+        self.age();
+        self.aging = policy.map(|policy| (policy, step));
+        self.ages.clear();
+        let flags: Vec<QueueFlag<P>> = self.entries.keys().cloned().collect();
+        for flag in flags {
+            self.schedule_aging(&flag);
+        }
+    }
+
+    /// Applies the aging policy, moving unlocked elements that have waited long enough up in the queue.
+    ///
+    /// This runs on every push and pop, so it only needs calling before a `peek` or `get_elements`
+    /// that should reflect the time spent waiting since then.
+    pub fn age(&mut self) {
+        let Some((policy, step)) = self.aging else {
+            return;
+        };
+        let now = self.clock.now();
+
+        // Only elements whose next level has come are looked at.
+        while let Some((due, id)) = self.ages.pop_due(now) {
+            let mut flag = self.index.get(&id).cloned().expect("aging an element missing from the queue");
+            let levels = policy.levels(flag.timestamp, now) - policy.levels(flag.timestamp, due) + 1;
+            let priority = step(flag.priority.clone(), levels);
+            if priority == flag.priority {
+                // Already as urgent as the step allows, so it has nothing left to age.
+                continue;
+            }
+
+            let item = self.entries.remove(&flag).expect("index out of sync with entries");
+            self.untrack(&flag);
+            flag.priority = priority;
+            self.insert(flag, item);
+        }
     }
 
     /// Pushes a prioritized item to the queue.
     /// Returns a unique identifier that can be used to track and retrieve this element in the queue
-    pub fn push(&mut self, item: Prio<T>) -> u32 {
-        let priority = item.priority().expect("Priority must be set");
+    ///
+    /// Fails if the item has no priority set.
+    pub fn push(&mut self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>> {
+        let Some(priority) = item.priority() else {
+            return Err(QueueError::MissingPriority(item));
+        };
+        self.refresh();
         let locked = item.locked;
 
-        let new_identifier = self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        let new_queue_flag = QueueFlag::new(priority, locked, new_identifier);
+        let Some(new_identifier) = self.next_identifier() else {
+            return Err(QueueError::IdsExhausted(item));
+        };
+        self.track_ttl(new_identifier, item.ttl());
+        self.insert(self.new_flag(priority, locked, new_identifier), item.item);
 
-        self.0.insert(new_queue_flag, item.item);
-
-        return new_identifier;
+        Ok(new_identifier)
     }
-    
+
     /// Pushes a non-prioritized item to the queue.
     /// No item pushed through this function will be locked
-    pub fn push_non_prio(&mut self, item: T) -> u32 {
-        let new_identifier = self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        let new_queue_flag = QueueFlag::new(self.size() as u32 + 1, LockStatus::Unlocked, new_identifier);
-        
-        self.0.insert(new_queue_flag, item);
+    ///
+    /// Panics if the queue has run out of identifiers.
+    pub fn push_non_prio(&mut self, item: T) -> QueueId
+    where
+        P: From<u32>,
+    {
+        self.refresh();
+        let new_identifier = self.next_identifier().expect("queue has run out of identifiers");
+        let new_queue_flag = self.new_flag(self.back_priority(), LockStatus::Unlocked, new_identifier);
         
-        return new_identifier;
+        self.insert(new_queue_flag, item);
         
+        new_identifier
     }
 
-    // Naive linear search through the entire queue
-    pub fn retrieve_by_id_linear(&self, id: u32) -> Result<T, String> {
-        for flag in self.0.keys() {
-            if flag.identifier == id {
-                return Ok(self.0.get(flag).cloned().unwrap());
-            }
-        }
-        Err(format!("ID {} not found in the queue", id))
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `delay` has passed.
+    /// Returns a unique identifier that can be used to track and retrieve this element in the queue
+    pub fn push_delayed(&mut self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>> {
+        self.push_at(item, Instant::now() + delay)
     }
 
-    pub fn retrieve_by_id(&self, id: u32) -> Result<T, String> {
-        // Check if the queue is empty
-        if self.0.is_empty() {
-            return Err("Queue is empty".to_string());
-        }
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `when`.
+    /// `when` can be an `Instant` or a `SystemTime`.
+    ///
+    /// Once due, the element is ordered as if it had been pushed at `when`.
+    ///
+    /// Delays are measured on the system's monotonic clock, not the queue's `Clock`: the element comes
+    /// due at `when`, and its timestamp is the queue's clock reading at push time plus the delay left until then.
+    pub fn push_at(&mut self, item: Prio<T, P>, when: impl Deadline) -> Result<QueueId, QueueError<T, P>> {
+        let Some(priority) = item.priority() else {
+            return Err(QueueError::MissingPriority(item));
+        };
+        self.refresh();
 
-        // Get the smallest and largest IDs in the queue
-        let first_flag = self.0.keys().next().unwrap(); // Get the first (smallest) QueueFlag
-        let last_flag = self.0.keys().next_back().unwrap(); // Get the last (largest) QueueFlag
+        let due = when.instant();
+        let Some(new_identifier) = self.next_identifier() else {
+            return Err(QueueError::IdsExhausted(item));
+        };
+        let mut flag = self.new_flag(priority, item.locked, new_identifier);
+        flag.timestamp += due.saturating_duration_since(Instant::now()).as_nanos() as u64;
+        self.track_ttl(new_identifier, item.ttl());
 
-        let first_id = first_flag.identifier;
-        let last_id = last_flag.identifier;
+        self.tally(&flag.priority);
+        self.scheduled.insert(due, flag, item.item);
+        Ok(new_identifier)
+    }
 
-        // Check if the id is out of bounds
-        if id < first_id || id > last_id {
-            return Err(format!(
-                "ID {} is not in the range of current queue ({} to {})",
-                id, first_id, last_id
-            ));
+    /// Returns when the earliest delayed element becomes due, or `None` if nothing is delayed.
+    /// Reserved elements count as delayed until their visibility timeout.
+    /// The returned time may be in the past if that element is already due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.scheduled.next_due()
+    }
+    
+    /// Pops the next item in the queue.
+    /// Expired elements in the way are dropped.
+    pub fn pop(&mut self) -> Option<T> {
+        let (flag, item) = self.take_next()?;
+        self.forget(flag.identifier, Instant::now());
+        Some(item)
+    }
+
+    /// Settles a lease by removing its element from the queue for good.
+    pub fn ack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        self.settle(lease)?;
+        self.forget(lease.id(), Instant::now());
+        Ok(())
+    }
+
+    /// Settles a lease by reporting that processing failed, and puts its element back into the queue
+    /// at its old place.
+    ///
+    /// With a dead-letter queue attached, an element that has failed `max_failures` times moves there instead.
+    pub fn nack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        let (flag, item) = self.settle(lease)?;
+        let id = flag.identifier;
+        let failures = self.failures.entry(id).or_default();
+        *failures += 1;
+        let failures = *failures;
+
+        let exhausted = self.dead_letter.as_ref().is_some_and(|(max_failures, _)| failures >= *max_failures);
+        if !exhausted {
+            self.insert(flag, item);
+            return Ok(());
         }
 
-        // Determine whether to start from the front or the back based on proximity
-        if id - first_id <= last_id - id {
-            // Start from the beginning and search forwards
-            for flag in self.0.keys() {
-                if flag.identifier == id {
-                    return Ok(self.0.get(flag).cloned().unwrap());
-                }
-            }
-        } else {
-            // Start from the end and search backwards
-            for flag in self.0.keys().rev() {
-                if flag.identifier == id {
-                    return Ok(self.0.get(flag).cloned().unwrap());
-                }
+        self.forget(id, Instant::now());
+        let (_, dead) = self.dead_letter.as_mut().expect("checked above");
+        dead.failures.insert(id, failures);
+        dead.insert(flag, item);
+        Ok(())
+    }
+
+    /// Returns how many times processing an element has been reported as failed through `nack`.
+    pub fn failures(&self, id: QueueId) -> u32 {
+        self.failures.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Attaches a dead-letter queue, which elements move to once they have failed `max_failures` times.
+    /// If one is already attached, only its limit changes.
+    ///
+    /// Dead-lettered elements keep their identifier, but lose their time-to-live.
+    pub fn set_dead_letter(&mut self, max_failures: u32) {
+        match &mut self.dead_letter {
+            Some((limit, _)) => *limit = max_failures,
+            None => {
+                let dead = Queue {
+                    origin: self.origin,
+                    ..Queue::with_order(self.order).with_clock(Arc::clone(&self.clock))
+                };
+                self.dead_letter = Some((max_failures, Box::new(dead)));
             }
         }
+    }
 
-        Err(format!("ID {} not found in the queue", id))
+    /// Returns the dead-letter queue, if one is attached.
+    pub fn dead_letters(&self) -> Option<&Queue<T, P>> {
+        self.dead_letter.as_ref().map(|(_, dead)| dead.as_ref())
     }
 
-    /// Pops the next item in the queue.
-    pub fn pop(&mut self) -> Option<T> {
-        let lowest_flag = self.0.keys().next().cloned();
+    /// Moves an element out of the dead-letter queue and back into this one, with its failures reset.
+    pub fn requeue_dead_letter(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.check_origin(id)?;
+        let (_, dead) = self.dead_letter.as_mut().ok_or(QueueError::NotFound(id))?;
+        let flag = dead.index.get(&id).cloned().ok_or(QueueError::NotFound(id))?;
+        let item = dead.remove(id).expect("index out of sync with entries");
+        dead.forget(id, Instant::now());
 
-        if let Some(flag) = lowest_flag {
-            let value = self.0.remove(&flag).unwrap();
-            return Some(value);
-        }
-        None
+        self.insert(flag, item);
+        Ok(())
+    }
+
+    /// Removes an element from the dead-letter queue for good.
+    pub fn drop_dead_letter(&mut self, id: QueueId) -> Option<T> {
+        let (_, dead) = self.dead_letter.as_mut()?;
+        dead.pop_by_id(id)
     }
     
     /// Pops an element based on the id
-    pub fn pop_by_id(&mut self, id: u32) -> Option<T> {
-        let flag = self.0.keys().find(|&f| f.identifier == id).cloned();
-        
-        if let Some(flag) = flag {
-            let value = self.0.remove(&flag).unwrap();
-            return Some(value);
+    /// Delayed elements can be popped this way before they are due.
+    /// Expired elements are dropped instead.
+    pub fn pop_by_id(&mut self, id: QueueId) -> Option<T> {
+        let item = self.remove(id)?;
+        if self.forget(id, Instant::now()) {
+            return None;
         }
-        None
+        Some(item)
+    }
+
+    /// Removes every expired element from the queue.
+    /// Returns the identifiers and elements that were dropped, in the order they expired.
+    pub fn purge_expired(&mut self) -> Vec<(QueueId, T)> {
+        let now = Instant::now();
+        let mut purged = Vec::new();
+        while let Some(id) = self.expiry.pop_expired(now) {
+            self.forget(id, now);
+            if let Some(item) = self.remove(id) {
+                purged.push((id, item));
+            }
+        }
+        purged
     }
     
+    /// Changes the priority of an element, keeping its identifier.
+    /// The element is re-sorted under its new priority.
+    pub fn reprioritize(&mut self, id: QueueId, priority: P, position: FifoPosition) -> Result<(), QueueError<T, P>> {
+        self.update(id, position, |flag| flag.priority = priority)
+    }
+
+    /// Locks an element in place, keeping its identifier and position.
+    pub fn lock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.update(id, FifoPosition::Keep, |flag| flag.locked = LockStatus::Locked)
+    }
+
+    /// Unlocks an element, keeping its identifier and position.
+    pub fn unlock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.update(id, FifoPosition::Keep, |flag| flag.locked = LockStatus::Unlocked)
+    }
+
     /// Peeks the next item in the queue.
     /// Returns a reference to the next item in the queue.
-    pub fn peek(&self) -> Option<(&T, u32)> {
-        let lowest_flag = self.0.keys().next().cloned();
-        let element = self.0.get(&lowest_flag.unwrap());
-        let identifier = lowest_flag.unwrap().identifier;
-        return element.map(|e| (e, identifier));
+    /// Expired elements are skipped.
+    pub fn peek(&self) -> Option<(&T, QueueId)> {
+        let now = Instant::now();
+        let live = |(flag, _): &(&QueueFlag<P>, &T)| !self.expiry.is_expired(flag.identifier, now);
+        let ready = self.entries.iter().find(live);
+        let due = self.scheduled.due_at(now).filter(live);
+        ready
+            .into_iter()
+            .chain(due)
+            .min_by_key(|(flag, _)| *flag)
+            .map(|(flag, item)| (item, flag.identifier))
     }
 
-    /// Returns all elements in the queue as a Vec.
-    pub fn get_elements(&self) -> Vec<T> {
-        self.0.values().cloned().collect()
+    /// Returns a handle to the next item in the queue, which can change it in place or pop it.
+    /// Returns `None` if nothing can be popped.
+    ///
+    /// Changes to the item's priority or lock are applied, and the item re-sorted, when the handle is dropped.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, P>> {
+        self.refresh();
+        let now = Instant::now();
+        let flag = self.entries.keys().find(|flag| !self.expiry.is_expired(flag.identifier, now))?.clone();
+        Some(PeekMut::new(self, flag))
     }
-    
-    /// Returns a copy of the entire queue
-    pub fn all(&self) -> BTreeMap<QueueFlag, T> {
-        self.0.clone()
+
+    /// Iterates over the elements in the queue in the order they would be popped, without cloning them.
+    /// Delayed elements that are not yet due, and expired elements, are left out.
+    pub fn iter(&self) -> Iter<'_, T, P> {
+        let now = Instant::now();
+        let mut due: Vec<_> = self.scheduled.due_at(now).collect();
+        due.sort_by_key(|(flag, _)| *flag);
+        Iter::new(self.entries.iter(), due, &self.expiry, now)
+    }
+
+    /// Iterates over the elements in the queue in the order they would be popped, allowing them to be changed.
+    /// Flags can't be changed this way, see `reprioritize`, `lock` and `unlock`.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, P> {
+        self.refresh();
+        let now = Instant::now();
+        let expiry = &self.expiry;
+        let elements: Vec<_> = self
+            .entries
+            .iter_mut()
+            .filter(|(flag, _)| !expiry.is_expired(flag.identifier, now))
+            .collect();
+        IterMut {
+            inner: elements.into_iter(),
+        }
     }
 
-    /// Returns the size of the queue
+    /// Removes every element `pop` can currently reach, and iterates over them in the order they would have been popped.
+    /// Delayed and reserved elements stay in the queue, expired elements are dropped.
+    ///
+    /// The elements are removed even if the iterator is dropped before it is used up.
+    pub fn drain(&mut self) -> Drain<T, P> {
+        self.refresh();
+        let now = Instant::now();
+        let elements: Vec<_> = mem::take(&mut self.entries)
+            .into_iter()
+            .filter(|(flag, _)| {
+                self.untrack(flag);
+                !self.forget(flag.identifier, now)
+            })
+            .collect();
+        Drain {
+            inner: elements.into_iter(),
+        }
+    }
+    
+    /// Returns the size of the queue, including delayed elements.
+    /// Expired elements are not counted.
     pub fn size(&self) -> usize {
-        self.0.len()
+        self.entries.len() + self.scheduled.len() - self.expiry.count_expired(Instant::now())
     }
 
-    /// Generates a new thread-safe LockableQueue
-    pub const fn thread_safe_new() -> LazyLock<RwLock<Mutex<Queue<T>>>> {
-        LazyLock::new(|| RwLock::new(Mutex::new(Queue::<T>::new()))) 
+    /// Hands out the next identifier, or `None` once the sequence numbers have run out.
+    fn next_identifier(&self) -> Option<QueueId> {
+        self.next_id
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |seq| seq.checked_add(1))
+            .ok()
+            .map(|seq| QueueId::new(self.origin, seq))
+    }
+
+    fn check_origin(&self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        match self.owns(id) {
+            true => Ok(()),
+            false => Err(QueueError::ForeignId(id)),
+        }
+    }
+
+    /// The priority `push_non_prio` gives an element: past the queue's size in a `MinFirst` queue,
+    /// and the lowest priority there is in a `MaxFirst` one.
+    fn back_priority(&self) -> P
+    where
+        P: From<u32>,
+    {
+        match self.order.direction {
+            Direction::MinFirst => P::from(self.size() as u32 + 1),
+            Direction::MaxFirst => P::from(0),
+        }
+    }
+
+    /// Moves due elements into the queue and applies the aging policy.
+    fn refresh(&mut self) {
+        let now = Instant::now();
+        while let Some((flag, item)) = self.scheduled.pop_due(now) {
+            self.leases.remove(&flag.identifier);
+            self.untally(&flag.priority);
+            self.insert(flag, item);
+        }
+        self.age();
+    }
+
+    /// The elements `pop` can currently reach, in no particular order.
+    fn visible(&self) -> impl Iterator<Item = (&QueueFlag<P>, &T)> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .chain(self.scheduled.due_at(now))
+            .filter(move |(flag, _)| !self.expiry.is_expired(flag.identifier, now))
+    }
+
+    /// Takes the next element out of the queue, dropping expired elements in the way.
+    fn take_next(&mut self) -> Option<(QueueFlag<P>, T)> {
+        self.refresh();
+        let now = Instant::now();
+        while let Some((flag, value)) = self.entries.pop_first() {
+            self.untrack(&flag);
+            if !self.expiry.is_expired(flag.identifier, now) {
+                return Some((flag, value));
+            }
+            self.forget(flag.identifier, now);
+        }
+        None
+    }
+
+    /// Takes a leased element out of the schedule, if the lease is still valid.
+    fn settle(&mut self, lease: Lease) -> Result<(QueueFlag<P>, T), QueueError<T, P>> {
+        let id = lease.id();
+        let valid = self.leases.get(&id) == Some(&lease.token())
+            && self.scheduled.due(id).is_some_and(|due| due > Instant::now());
+        if !valid {
+            return Err(QueueError::LeaseExpired(id));
+        }
+
+        self.leases.remove(&id);
+        let (flag, item) = self.scheduled.remove(id).expect("lease without a scheduled element");
+        self.untally(&flag.priority);
+        Ok((flag, item))
+    }
+
+    fn track_ttl(&mut self, id: QueueId, ttl: Option<Duration>) {
+        if let Some(ttl) = ttl {
+            self.expiry.insert(id, Instant::now() + ttl);
+        }
+    }
+
+    /// Drops what the queue tracks about an element that has left it for good.
+    /// Returns whether the element had expired at `now`.
+    fn forget(&mut self, id: QueueId, now: Instant) -> bool {
+        self.leases.remove(&id);
+        self.failures.remove(&id);
+        self.expiry.remove(id, now)
+    }
+
+    /// Takes an element out of the queue, whether it is due or not.
+    fn remove(&mut self, id: QueueId) -> Option<T> {
+        match self.index.get(&id).cloned() {
+            Some(flag) => {
+                let item = self.entries.remove(&flag)?;
+                self.untrack(&flag);
+                Some(item)
+            }
+            None => {
+                let (flag, item) = self.scheduled.remove(id)?;
+                self.untally(&flag.priority);
+                Some(item)
+            }
+        }
+    }
+
+    fn update(&mut self, id: QueueId, position: FifoPosition, change: impl FnOnce(&mut QueueFlag<P>)) -> Result<(), QueueError<T, P>> {
+        self.check_origin(id)?;
+        self.refresh();
+        let Some(mut flag) = self.index.get(&id).cloned() else {
+            // Delayed elements are re-sorted when they become due, and are stamped with their due time.
+            let (flag, _) = self.scheduled.get_mut(id).ok_or(QueueError::NotFound(id))?;
+            let before = flag.priority.clone();
+            change(flag);
+            let after = flag.priority.clone();
+            self.untally(&before);
+            self.tally(&after);
+            return Ok(());
+        };
+        let item = self.entries.remove(&flag).expect("index out of sync with entries");
+        self.untrack(&flag);
+
+        change(&mut flag);
+        if position == FifoPosition::Reset {
+            flag.timestamp = self.clock.now();
+        }
+
+        self.insert(flag, item);
+        Ok(())
+    }
+
+    /// Creates a flag stamped with the queue's clock and order.
+    fn new_flag(&self, priority: P, locked: LockStatus, id: QueueId) -> QueueFlag<P> {
+        let mut flag = QueueFlag::new(priority, locked, id);
+        flag.timestamp = self.clock.now();
+        flag.order = self.order;
+        flag
+    }
+
+    fn insert(&mut self, mut flag: QueueFlag<P>, item: T) {
+        flag.order = self.order;
+        self.tally(&flag.priority);
+        self.schedule_aging(&flag);
+        self.index.insert(flag.identifier, flag.clone());
+        self.entries.insert(flag, item);
+    }
+
+    /// Drops what is tracked about an element taken out of `entries`.
+    fn untrack(&mut self, flag: &QueueFlag<P>) {
+        self.index.remove(&flag.identifier);
+        self.untally(&flag.priority);
+        self.ages.remove(flag.identifier);
+    }
+
+    /// Notes when an unlocked element in `entries` next moves up a level, if aging is on.
+    fn schedule_aging(&mut self, flag: &QueueFlag<P>) {
+        if let (Some((policy, _)), LockStatus::Unlocked) = (self.aging, flag.locked) {
+            self.ages.insert(flag.identifier, policy.next_level(flag.timestamp, self.clock.now()));
+        }
+    }
+
+    /// Counts an element coming into `entries` or the schedule.
+    fn tally(&mut self, priority: &P) {
+        match self.priorities.get_mut(priority) {
+            Some(count) => *count += 1,
+            None => {
+                self.priorities.insert(priority.clone(), 1);
+            }
+        }
+    }
+
+    /// Stops counting an element leaving `entries` or the schedule.
+    fn untally(&mut self, priority: &P) {
+        if let Some(count) = self.priorities.get_mut(priority) {
+            *count -= 1;
+            if *count == 0 {
+                self.priorities.remove(priority);
+            }
+        }
+    }
+
+    /// Counts the live elements, due or not, that pop at `priority` or after it.
+    fn count_behind(&self, priority: &P, now: Instant) -> usize {
+        let direction = self.order.direction;
+        let behind = match direction {
+            Direction::MinFirst => (Bound::Included(priority), Bound::Unbounded),
+            Direction::MaxFirst => (Bound::Unbounded, Bound::Included(priority)),
+        };
+        let held: usize = self.priorities.range::<P, _>(behind).map(|(_, count)| count).sum();
+        let expired = self
+            .expiry
+            .expired(now)
+            .filter_map(|id| self.index.get(&id).or_else(|| self.scheduled.get(id).map(|(flag, _)| flag)))
+            .filter(|flag| direction.cmp(&flag.priority, priority) != CmpOrdering::Less)
+            .count();
+        held - expired
+    }
+}
+
+impl<T, P> Queue<T, P>
+where
+    T: Queueable + Clone,
+    P: Ord + Clone,
+{
+    // Naive linear search through the entire queue
+    pub fn retrieve_by_id_linear(&self, id: QueueId) -> Result<T, QueueError<T, P>> {
+        self.check_origin(id)?;
+        let now = Instant::now();
+        for (flag, item) in self.entries.iter().chain(self.scheduled.iter()) {
+            if flag.identifier == id && !self.expiry.is_expired(id, now) {
+                return Ok(item.clone());
+            }
+        }
+        Err(QueueError::NotFound(id))
+    }
+
+    /// Returns a copy of the element with the given identifier.
+    pub fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T, P>> {
+        self.check_origin(id)?;
+        if self.size() == 0 {
+            return Err(QueueError::Empty);
+        }
+        if self.expiry.is_expired(id, Instant::now()) {
+            return Err(QueueError::NotFound(id));
+        }
+
+        self.index
+            .get(&id)
+            .and_then(|flag| self.entries.get(flag))
+            .or_else(|| self.scheduled.get(id).map(|(_, item)| item))
+            .cloned()
+            .ok_or(QueueError::NotFound(id))
+    }
+
+    /// Hands out the next item in the queue without removing it.
+    ///
+    /// The item is hidden from other consumers until the lease is settled with `ack` or `nack`.
+    /// If neither happens within `visibility_timeout`, it goes back into the queue at its old place.
+    pub fn reserve(&mut self, visibility_timeout: Duration) -> Option<(T, Lease)> {
+        let (flag, item) = self.take_next()?;
+        let lease = Lease::new(flag.identifier, self.next_lease);
+        self.next_lease += 1;
+
+        self.leases.insert(flag.identifier, lease.token());
+        self.tally(&flag.priority);
+        self.scheduled.insert(Instant::now() + visibility_timeout, flag, item.clone());
+        Some((item, lease))
+    }
+
+    /// Returns all elements in the queue as a Vec, in the order they would be popped.
+    /// Delayed elements that are not yet due, and expired elements, are left out.
+    pub fn get_elements(&self) -> Vec<T> {
+        self.iter().map(|(_, item)| item.clone()).collect()
+    }
+
+    /// Returns a copy of the entire queue, including delayed and expired elements.
+    pub fn all(&self) -> BTreeMap<QueueFlag<P>, T> {
+        self.entries
+            .iter()
+            .chain(self.scheduled.iter())
+            .map(|(flag, item)| (flag.clone(), item.clone()))
+            .collect()
+    }
+}
+
+impl<T, P> Default for Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn default() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            index: HashMap::new(),
+            scheduled: Schedule::new(),
+            priorities: BTreeMap::new(),
+            leases: HashMap::new(),
+            next_lease: 0,
+            failures: HashMap::new(),
+            dead_letter: None,
+            expiry: Expiry::new(),
+            origin: rand::random(),
+            next_id: AtomicU64::new(0),
+            order: OrderPolicy::default(),
+            clock: Arc::new(MonotonicClock),
+            aging: None,
+            ages: Ages::new(),
+        }
+    }
+}
+
+impl<T, P> PriorityQueue<T, P> for Queue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn order(&self) -> OrderPolicy {
+        self.order()
+    }
+
+    fn owns(&self, id: QueueId) -> bool {
+        self.owns(id)
+    }
+
+    fn set_aging_with(&mut self, policy: Option<AgingPolicy>, step: AgingStep<P>) {
+        self.set_aging_with(policy, step)
+    }
+
+    fn age(&mut self) {
+        self.age()
+    }
+
+    fn push(&mut self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>> {
+        self.push(item)
+    }
+
+    fn push_non_prio(&mut self, item: T) -> Result<QueueId, QueueError<T, P>>
+    where
+        P: From<u32>,
+    {
+        Ok(self.push_non_prio(item))
+    }
+
+    fn push_delayed(&mut self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>> {
+        self.push_delayed(item, delay)
+    }
+
+    fn push_at<D>(&mut self, item: Prio<T, P>, when: D) -> Result<QueueId, QueueError<T, P>>
+    where
+        D: Deadline,
+    {
+        self.push_at(item, when)
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.next_due()
+    }
+
+    fn retrieve_by_id_linear(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone,
+    {
+        self.retrieve_by_id_linear(id)
+    }
+
+    fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone,
+    {
+        self.retrieve_by_id(id)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop()
+    }
+
+    fn reserve(&mut self, visibility_timeout: Duration) -> Option<(T, Lease)>
+    where
+        T: Clone,
+    {
+        self.reserve(visibility_timeout)
+    }
+
+    fn ack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        self.ack(lease)
+    }
+
+    fn nack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        self.nack(lease)
+    }
+
+    fn failures(&self, id: QueueId) -> u32 {
+        self.failures(id)
+    }
+
+    fn set_dead_letter(&mut self, max_failures: u32) {
+        self.set_dead_letter(max_failures)
+    }
+
+    fn dead_letters(&self) -> Option<&dyn PriorityQueue<T, P>> {
+        self.dead_letters().map(|dead| dead as &dyn PriorityQueue<T, P>)
+    }
+
+    fn requeue_dead_letter(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.requeue_dead_letter(id)
+    }
+
+    fn drop_dead_letter(&mut self, id: QueueId) -> Option<T> {
+        self.drop_dead_letter(id)
+    }
+
+    fn pop_by_id(&mut self, id: QueueId) -> Option<T> {
+        self.pop_by_id(id)
+    }
+
+    fn purge_expired(&mut self) -> Vec<(QueueId, T)> {
+        self.purge_expired()
+    }
+
+    fn reprioritize(&mut self, id: QueueId, priority: P, position: FifoPosition) -> Result<(), QueueError<T, P>> {
+        self.reprioritize(id, priority, position)
+    }
+
+    fn lock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.lock(id)
+    }
+
+    fn unlock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.unlock(id)
+    }
+
+    fn peek(&self) -> Option<(&T, QueueId)> {
+        self.peek()
+    }
+
+    fn get_elements(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.get_elements()
+    }
+
+    fn all(&self) -> BTreeMap<QueueFlag<P>, T>
+    where
+        T: Clone,
+    {
+        self.all()
+    }
+
+    fn size(&self) -> usize {
+        self.size()
     }
 }
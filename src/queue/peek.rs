@@ -0,0 +1,122 @@
+//! A handle to the head of a `Queue` that can change it in place.
+
+use std::{
+    ops::{Deref, DerefMut},
+    time::Instant,
+};
+
+use super::{
+    prelude::{FifoPosition, LockStatus, QueueFlag, QueueId, Queueable},
+    Queue,
+};
+
+
+/// The next element of a queue, borrowed mutably, see `Queue::peek_mut`.
+///
+/// The payload can be changed through `Deref`. Changes to the priority or lock
+/// are applied when the handle is dropped, re-sorting the element.
+///
+/// Like `BinaryHeap::PeekMut`, the handle's own functions are associated functions,
+/// so they never shadow methods of the payload: `PeekMut::pop(head)`.
+pub struct PeekMut<'a, T, P = u32>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    queue: &'a mut Queue<T, P>,
+    /// The flag the element is stored under.
+    original: QueueFlag<P>,
+    /// The flag the element will be stored under once the handle is dropped.
+    flag: QueueFlag<P>,
+    position: FifoPosition,
+}
+
+impl<'a, T, P> PeekMut<'a, T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    pub(super) fn new(queue: &'a mut Queue<T, P>, flag: QueueFlag<P>) -> Self {
+        Self {
+            queue,
+            original: flag.clone(),
+            flag,
+            position: FifoPosition::Keep,
+        }
+    }
+
+    /// Returns the identifier of the element.
+    pub fn id(this: &Self) -> QueueId {
+        this.original.identifier
+    }
+
+    /// Returns the flag the element will be sorted by, including changes not yet applied.
+    pub fn flag(this: &Self) -> &QueueFlag<P> {
+        &this.flag
+    }
+
+    /// Changes the priority of the element once the handle is dropped.
+    pub fn reprioritize(this: &mut Self, priority: P, position: FifoPosition) {
+        this.flag.priority = priority;
+        this.position = position;
+    }
+
+    /// Locks or unlocks the element once the handle is dropped.
+    pub fn set_lock(this: &mut Self, locked: LockStatus) {
+        this.flag.locked = locked;
+    }
+
+    /// Removes the element from the queue and returns it, discarding any changes to its flag.
+    pub fn pop(mut this: Self) -> T {
+        let id = this.original.identifier;
+        let item = this.queue.entries.remove(&this.original).expect("peeked element missing from the queue");
+        this.queue.untrack(&this.original);
+        this.queue.forget(id, Instant::now());
+
+        // Nothing is left for the drop to re-sort.
+        this.flag = this.original.clone();
+        this.position = FifoPosition::Keep;
+        item
+    }
+}
+
+impl<T, P> Deref for PeekMut<'_, T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.queue.entries[&self.original]
+    }
+}
+
+impl<T, P> DerefMut for PeekMut<'_, T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.queue.entries.get_mut(&self.original).expect("peeked element missing from the queue")
+    }
+}
+
+impl<T, P> Drop for PeekMut<'_, T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn drop(&mut self) {
+        if self.flag == self.original && self.position == FifoPosition::Keep {
+            return;
+        }
+
+        let item = self.queue.entries.remove(&self.original).expect("peeked element missing from the queue");
+        self.queue.untrack(&self.original);
+        if self.position == FifoPosition::Reset {
+            self.flag.timestamp = self.queue.clock.now();
+        }
+        self.queue.insert(self.flag.clone(), item);
+    }
+}
@@ -1,59 +1,86 @@
 use std::{
     cmp::Ordering,
-    time::{SystemTime, UNIX_EPOCH},
+    fmt,
+    time::{Duration, Instant, SystemTime},
 };
 
+use super::clock::{Clock, MonotonicClock};
 
-pub trait Queueable: Clone {}
+
+/// Anything that can be held by a queue.
+///
+/// Every type is queueable, there is nothing to implement. Only a few methods, such as `retrieve_by_id`
+/// and `get_elements`, hand out copies and need the elements to be `Clone`.
+pub trait Queueable {}
+
+impl<T> Queueable for T {}
 
 
 
 /// QueueFlags are designed to be used behing the scenes
 /// They hold more information on their spot in the queue.
+///
+/// The priority can be any `Ord + Clone` type, and defaults to `u32`. Lower priorities come out first.
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
-pub struct QueueFlag {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueFlag<P = u32> {
     /// Whether or not the item can be shifted
     pub locked: LockStatus,
     /// The priority of the item in which it will try to populate.
-    pub priority: u32,
+    pub priority: P,
     /// This is an identifier hash.
-    pub identifier: u32,
-    /// time of which this item was added to the queue
+    pub identifier: QueueId,
+    /// time of which this item was added to the queue, in nanoseconds since the Unix epoch
     pub timestamp: u64,
+    /// The order of the queue holding the item.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) order: OrderPolicy,
+    /// Breaks ties between equal flags under `TieBreak::Random`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) salt: u64,
 }
 
-impl QueueFlag {
-    pub fn new(priority: u32, lock: LockStatus, id: u32) -> Self {
-        let now = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards");
+impl<P> QueueFlag<P> {
+    pub fn new(priority: P, lock: LockStatus, id: QueueId) -> Self {
         Self {
             priority,
             identifier: id,
             locked: lock,
-            timestamp: now.as_secs(), // Set the timestamp
+            timestamp: MonotonicClock.now(), // Set the timestamp
+            order: OrderPolicy::default(),
+            salt: rand::random(),
         }
     }
 }
 
-impl PartialOrd for QueueFlag {
+impl<P> PartialOrd for QueueFlag<P>
+where
+    P: Ord,
+{
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for QueueFlag {
+impl<P> Ord for QueueFlag<P>
+where
+    P: Ord,
+{
     fn cmp(&self, other: &Self) -> Ordering {
         // Compare by priority first
-        match self.priority.cmp(&other.priority) {
+        match self.order.direction.cmp(&self.priority, &other.priority) {
             Ordering::Equal => {
                 // Prioritize locked items before unlocked
                 match (self.locked, other.locked) {
                     (LockStatus::Locked, LockStatus::Unlocked) => Ordering::Less,
                     (LockStatus::Unlocked, LockStatus::Locked) => Ordering::Greater,
                     _ => {
-                        // Fallback to timestamp if both are locked/unlocked
-                        match self.timestamp.cmp(&other.timestamp) {
-                            Ordering::Equal => self.identifier.cmp(&other.identifier),
-                            other => other,
+                        // Fallback to the tie-break if both are locked/unlocked
+                        let age = (self.timestamp, self.identifier).cmp(&(other.timestamp, other.identifier));
+                        match self.order.ties {
+                            TieBreak::Fifo => age,
+                            TieBreak::Lifo => age.reverse(),
+                            TieBreak::Random => (self.salt, self.identifier).cmp(&(other.salt, other.identifier)),
                         }
                     }
                 }
@@ -63,75 +90,363 @@ impl Ord for QueueFlag {
     }
 }
 
+/// Which end of the priority range a queue pops first.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// The lowest priority comes out first, so 0 is the most urgent.
+    #[default]
+    MinFirst,
+    /// The highest priority comes out first.
+    MaxFirst,
+}
+
+impl Direction {
+    /// Compares two priorities, with the one popped first as the lesser.
+    pub fn cmp<P>(&self, a: &P, b: &P) -> Ordering
+    where
+        P: Ord,
+    {
+        match self {
+            Direction::MinFirst => a.cmp(b),
+            Direction::MaxFirst => b.cmp(a),
+        }
+    }
+}
+
+/// How elements of the same priority and lock status are ordered.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TieBreak {
+    /// The oldest element comes out first.
+    #[default]
+    Fifo,
+    /// The newest element comes out first.
+    Lifo,
+    /// Elements come out in a random order, drawn once when they are pushed.
+    Random,
+}
+
+/// How a queue orders its elements, set when the queue is created.
+///
+/// Locked elements always come before unlocked elements of the same priority.
+/// The default pops the lowest priority first, oldest first.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderPolicy {
+    pub direction: Direction,
+    pub ties: TieBreak,
+}
+
+impl OrderPolicy {
+    pub fn new(direction: Direction, ties: TieBreak) -> Self {
+        Self { direction, ties }
+    }
+}
+
+/// A point in time an element can be scheduled for.
+pub trait Deadline {
+    /// The deadline as an `Instant`. Deadlines in the past are returned as-is or clamped to now.
+    fn instant(&self) -> Instant;
+}
+
+impl Deadline for Instant {
+    fn instant(&self) -> Instant {
+        *self
+    }
+}
+
+impl Deadline for SystemTime {
+    fn instant(&self) -> Instant {
+        let now = Instant::now();
+        match self.duration_since(SystemTime::now()) {
+            Ok(ahead) => now + ahead,
+            Err(behind) => now.checked_sub(behind.duration()).unwrap_or(now),
+        }
+    }
+}
+
+/// Identifies an element for as long as it is in the queue.
+///
+/// Each queue draws a random origin when it is created, and numbers its elements from there,
+/// so an identifier from one queue is never mistaken for an element of another.
+/// Sequence numbers are 64 bits wide and are never reused.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueId {
+    pub(crate) origin: u64,
+    pub(crate) seq: u64,
+}
+
+impl QueueId {
+    /// Sorts after every other identifier.
+    pub(crate) const MAX: QueueId = QueueId {
+        origin: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    pub(crate) fn new(origin: u64, seq: u64) -> Self {
+        Self { origin, seq }
+    }
+
+    /// The origin of the queue that handed out this identifier.
+    pub fn origin(&self) -> u64 {
+        self.origin
+    }
+
+    /// The position of the element in the order its queue handed out identifiers.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+impl fmt::Display for QueueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}:{}", self.origin, self.seq)
+    }
+}
+
+/// Proof that an element was handed out by `reserve`, used to `ack` or `nack` it.
+///
+/// A lease stops being valid once it is settled, or once its visibility timeout runs out
+/// and the element goes back into the queue.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub struct Lease {
+    id: QueueId,
+    token: u64,
+}
+
+impl Lease {
+    pub(crate) fn new(id: QueueId, token: u64) -> Self {
+        Self { id, token }
+    }
+
+    /// The identifier of the leased element.
+    pub fn id(&self) -> QueueId {
+        self.id
+    }
+
+    pub(crate) fn token(&self) -> u64 {
+        self.token
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LockStatus {
     Unlocked,
     Locked,
 }
 
+/// What happens to an element's place in line when it is moved within the queue.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FifoPosition {
+    /// The element keeps its original timestamp, staying ahead of anything pushed after it.
+    Keep,
+    /// The element is stamped with the current time, as if it had just been pushed.
+    Reset,
+}
+
+/// What a `SizedQueue` does with `push_evicting` when it is full.
+///
+/// Only unlocked elements waiting in line are ever evicted. Locked, delayed and reserved elements stay put.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    /// Reject the new element with `QueueError::Full`.
+    #[default]
+    Reject,
+    /// Evict the lowest-priority unlocked element, if the new element has a strictly better priority.
+    EvictLowest,
+    /// Evict the unlocked element that has been waiting the longest.
+    DropOldest,
+    /// Evict the unlocked element that was pushed most recently.
+    DropNewest,
+}
 
+/// What `SizedQueue::resize` does when the queue holds more elements than the new limit.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShrinkPolicy {
+    /// Leave the queue as it is and fail with `QueueError::Overfull`.
+    Refuse,
+    /// Remove the lowest-priority unlocked elements until the queue fits, and hand them back.
+    /// Fails like `Refuse` if locked, delayed or reserved elements alone are over the limit.
+    Truncate,
+    /// Set the new limit anyway. Pushes are rejected until enough elements have left the queue.
+    AllowOverflow,
+}
+
+/// A cap on how much of a `SizedQueue` elements of lower priority may fill,
+/// keeping the rest of it free for more urgent work.
+///
+/// The band covers `priority` and every priority popped after it, so `AdmissionBand::new(6, 70)`
+/// stops priorities 6 and up from being pushed once they take up 70% of the queue.
+/// In a `Direction::MaxFirst` queue, it would cover 6 and down.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdmissionBand<P = u32> {
+    /// The most urgent priority in the band.
+    pub priority: P,
+    /// How much of the queue's maximum size the band may fill, in percent.
+    pub percent: u8,
+}
 
-#[derive(Clone, Debug)]
-pub struct Prio<T>
+impl<P> AdmissionBand<P>
 where
-    T: Queueable + Clone,
+    P: Ord,
+{
+    pub fn new(priority: P, percent: u8) -> Self {
+        Self { priority, percent }
+    }
+
+    /// Whether an element of `priority` falls in this band, in a queue popping in `direction`.
+    pub fn covers(&self, priority: &P, direction: Direction) -> bool {
+        direction.cmp(priority, &self.priority) != Ordering::Less
+    }
+
+    /// How many elements the band may hold in a queue of `max_size`.
+    pub fn slots(&self, max_size: u32) -> u32 {
+        (u64::from(max_size) * u64::from(self.percent.min(100)) / 100) as u32
+    }
+}
+
+/// A fill level crossing reported by `SizedQueue` watermarks, with the percentage the queue was full at.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WatermarkEvent {
+    /// The queue filled up to the high watermark.
+    High(f32),
+    /// The queue drained back down to the low watermark, after reaching the high one.
+    Low(f32),
+}
+
+/// An opt-in policy that keeps unlocked elements from starving behind a steady stream of higher priorities.
+///
+/// Every `interval` an `Unlocked` element spends waiting, its priority improves by one level, down to 0
+/// (or up to `u32::MAX` in a `Direction::MaxFirst` queue).
+/// `Locked` elements keep the priority they were pinned to.
+///
+/// The queue keeps track of when each unlocked element next moves up a level, so only the elements
+/// whose time has come are touched, and pushing or popping costs an extra `O(log n)` while aging is on.
+///
+/// For priority types other than `u32`, what one level means is up to the step given to `Queue::set_aging_with`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgingPolicy {
+    /// How long an element waits for each level it moves up, as measured by the queue's `Clock`.
+    pub interval: Duration,
+}
+
+/// Moves a priority up by a number of levels, for aging queues whose priorities aren't `u32`.
+pub type AgingStep<P> = fn(P, u32) -> P;
+
+impl AgingPolicy {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+
+    /// How many levels an element stamped at `timestamp` has aged by `now`.
+    pub(crate) fn levels(&self, timestamp: u64, now: u64) -> u32 {
+        let step = (self.interval.as_nanos() as u64).max(1);
+        (now.saturating_sub(timestamp) / step).try_into().unwrap_or(u32::MAX)
+    }
+
+    /// When an element stamped at `timestamp` moves up its next level after `now`.
+    pub(crate) fn next_level(&self, timestamp: u64, now: u64) -> u64 {
+        let step = (self.interval.as_nanos() as u64).max(1);
+        let levels = now.saturating_sub(timestamp) / step;
+        timestamp.saturating_add(levels.saturating_add(1).saturating_mul(step))
+    }
+}
+
+
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Prio<T, P = u32>
+where
+    T: Queueable,
 {
     pub item: T,
-    priority: Option<u32>,
+    priority: Option<P>,
     pub locked: LockStatus,
+    ttl: Option<Duration>,
 }
 
-impl<T> Queueable for Prio<T> where T: Queueable + Clone {}
-
-impl<T> Prio<T>
+impl<T, P> Prio<T, P>
 where
-    T: Queueable + Clone,
+    T: Queueable,
 {
-    pub fn new(item: T, priority: Option<u32>, lock: LockStatus) -> Self {
+    pub fn new(item: T, priority: Option<P>, lock: LockStatus) -> Self {
         Self {
             item,
             priority,
             locked: lock,
+            ttl: None,
         }
     }
 
-    /// Creates a new prio with a lock and a instant priority ( 0 )
-    pub fn wlip(item: T) -> Self {
+    /// Creates a new prio with a lock.
+    pub fn wlock(item: T, priority: Option<P>) -> Self {
         Self {
             item,
-            priority: Some(0),
+            priority,
             locked: LockStatus::Locked,
+            ttl: None,
         }
     }
 
-    /// Creates a new prio with no lock and a instant priority ( 0 )
-    pub fn wolip(item: T) -> Self {
+    /// Creates a new prio with no lock.
+    pub fn wolock(item: T, priority: Option<P>) -> Self {
         Self {
             item,
-            priority: Some(0),
+            priority,
             locked: LockStatus::Unlocked,
+            ttl: None,
         }
     }
 
-    /// Creates a new prio with a lock.
-    pub fn wlock(item: T, priority: Option<u32>) -> Self {
+    /// Sets how long the item stays worth popping once pushed.
+    /// After that it is skipped by `pop`, `peek` and `get_elements`, and handed out by `purge_expired`.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn priority(&self) -> Option<P>
+    where
+        P: Clone,
+    {
+        self.priority.clone()
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+impl<T> Prio<T>
+where
+    T: Queueable,
+{
+    /// Creates a new prio with a lock and a instant priority ( 0 )
+    pub fn wlip(item: T) -> Self {
         Self {
             item,
-            priority,
+            priority: Some(0),
             locked: LockStatus::Locked,
+            ttl: None,
         }
     }
 
-    /// Creates a new prio with no lock.
-    pub fn wolock(item: T, priority: Option<u32>) -> Self {
+    /// Creates a new prio with no lock and a instant priority ( 0 )
+    pub fn wolip(item: T) -> Self {
         Self {
             item,
-            priority,
+            priority: Some(0),
             locked: LockStatus::Unlocked,
+            ttl: None,
         }
     }
-
-    pub fn priority(&self) -> Option<u32> {
-        self.priority
-    }
 }
@@ -0,0 +1,94 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Instant,
+};
+
+use super::prelude::{QueueFlag, QueueId, Queueable};
+
+
+/// Elements held aside until a point in time, kept in the order they become due.
+pub(crate) struct Schedule<T, P>
+where
+    T: Queueable,
+{
+    by_due: BTreeMap<(Instant, QueueId), (QueueFlag<P>, T)>,
+    due: HashMap<QueueId, Instant>,
+}
+
+impl<T, P> Schedule<T, P>
+where
+    T: Queueable,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            by_due: BTreeMap::new(),
+            due: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, due: Instant, flag: QueueFlag<P>, item: T) {
+        self.due.insert(flag.identifier, due);
+        self.by_due.insert((due, flag.identifier), (flag, item));
+    }
+
+    pub(crate) fn due(&self, id: QueueId) -> Option<Instant> {
+        self.due.get(&id).copied()
+    }
+
+    pub(crate) fn get(&self, id: QueueId) -> Option<&(QueueFlag<P>, T)> {
+        let due = self.due.get(&id)?;
+        self.by_due.get(&(*due, id))
+    }
+
+    pub(crate) fn get_mut(&mut self, id: QueueId) -> Option<&mut (QueueFlag<P>, T)> {
+        let due = self.due.get(&id)?;
+        self.by_due.get_mut(&(*due, id))
+    }
+
+    pub(crate) fn remove(&mut self, id: QueueId) -> Option<(QueueFlag<P>, T)> {
+        let due = self.due.remove(&id)?;
+        self.by_due.remove(&(due, id))
+    }
+
+    /// Removes the earliest element that is due at `now`.
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Option<(QueueFlag<P>, T)> {
+        let entry = self.by_due.first_entry()?;
+        if entry.key().0 > now {
+            return None;
+        }
+        let (flag, item) = entry.remove();
+        self.due.remove(&flag.identifier);
+        Some((flag, item))
+    }
+
+    /// The elements that are due at `now` but not yet taken out.
+    pub(crate) fn due_at(&self, now: Instant) -> impl Iterator<Item = (&QueueFlag<P>, &T)> {
+        self.by_due
+            .iter()
+            .take_while(move |((due, _), _)| *due <= now)
+            .map(|(_, (flag, item))| (flag, item))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&QueueFlag<P>, &T)> {
+        self.by_due.values().map(|(flag, item)| (flag, item))
+    }
+
+    pub(crate) fn into_values(self) -> impl Iterator<Item = (QueueFlag<P>, T)> {
+        self.by_due.into_values()
+    }
+
+    /// Every element along with when it is due, in due order.
+    #[cfg(feature = "serde")]
+    pub(crate) fn iter_due(&self) -> impl Iterator<Item = (Instant, &QueueFlag<P>, &T)> {
+        self.by_due.iter().map(|((due, _), (flag, item))| (*due, flag, item))
+    }
+
+    /// When the earliest element becomes due.
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        self.by_due.keys().next().map(|(due, _)| *due)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_due.len()
+    }
+}
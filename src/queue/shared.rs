@@ -0,0 +1,159 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use super::{
+    error::QueueError,
+    prelude::{Deadline, Prio, QueueId, Queueable},
+    Queue,
+};
+
+
+/// A SharedQueue is a `Queue` that can be used from many threads at once.
+///
+/// Cloning a SharedQueue gives another handle to the same queue.
+/// Consumers waiting in `pop` or `pop_timeout` are woken up as soon as an item is pushed,
+/// or when a delayed item becomes due.
+///
+/// Items come out in the same Priority, Lock, Time order as `Queue`.
+pub struct SharedQueue<T, P = u32>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    inner: Arc<Shared<T, P>>,
+}
+
+struct Shared<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    queue: Mutex<Queue<T, P>>,
+    available: Condvar,
+}
+
+impl<T> SharedQueue<T>
+where
+    T: Queueable,
+{
+    /// Creates a new, empty SharedQueue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, P> SharedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// Pushes a prioritized item to the queue, waking one waiting consumer.
+    /// Returns a unique identifier that can be used to track and retrieve this element in the queue
+    pub fn push(&self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>> {
+        let id = self.lock().push(item)?;
+        self.inner.available.notify_one();
+        Ok(id)
+    }
+
+    /// Pushes a prioritized item that stays hidden from `pop` until `delay` has passed.
+    pub fn push_delayed(&self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>> {
+        self.push_at(item, Instant::now() + delay)
+    }
+
+    /// Pushes a prioritized item that stays hidden from `pop` until `when`.
+    pub fn push_at(&self, item: Prio<T, P>, when: impl Deadline) -> Result<QueueId, QueueError<T, P>> {
+        let id = self.lock().push_at(item, when)?;
+        // Waiting consumers may need to wake up sooner than they planned to.
+        self.inner.available.notify_all();
+        Ok(id)
+    }
+
+    /// Pops the next item in the queue, blocking until one is available.
+    pub fn pop(&self) -> T {
+        self.pop_until(None).expect("woken with an empty queue")
+    }
+
+    /// Pops the next item in the queue, waiting at most `timeout` for one to arrive.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        self.pop_until(Some(Instant::now() + timeout))
+    }
+
+    /// Pops the next item in the queue without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        self.lock().pop()
+    }
+
+    /// Returns the size of the queue
+    pub fn size(&self) -> usize {
+        self.lock().size()
+    }
+
+    fn pop_until(&self, deadline: Option<Instant>) -> Option<T> {
+        let mut queue = self.lock();
+        loop {
+            if let Some(item) = queue.pop() {
+                return Some(item);
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                return None;
+            }
+
+            let wake_at = match (queue.next_due(), deadline) {
+                (Some(due), Some(deadline)) => Some(due.min(deadline)),
+                (due, deadline) => due.or(deadline),
+            };
+            queue = match wake_at {
+                Some(wake_at) => {
+                    let timeout = wake_at.saturating_duration_since(now);
+                    self.inner.available.wait_timeout(queue, timeout).expect("queue lock poisoned").0
+                }
+                None => self.inner.available.wait(queue).expect("queue lock poisoned"),
+            };
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue<T, P>> {
+        self.inner.queue.lock().expect("queue lock poisoned")
+    }
+}
+
+impl<T, P> From<Queue<T, P>> for SharedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn from(queue: Queue<T, P>) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                queue: Mutex::new(queue),
+                available: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl<T, P> Clone for SharedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T, P> Default for SharedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn default() -> Self {
+        Self::from(Queue::default())
+    }
+}
@@ -1,17 +1,46 @@
 use std::{
     collections::BTreeMap,
-    sync::atomic::AtomicU32,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    clock::Clock,
+    error::QueueError,
+    iter::{Drain, IntoIter, Iter, IterMut},
+    traits::{BoundedQueue, PriorityQueue},
+    prelude::{AdmissionBand, AgingPolicy, AgingStep, Deadline, FifoPosition, Lease, LockStatus, OrderPolicy, OverflowPolicy, Prio, QueueFlag, QueueId, Queueable, ShrinkPolicy, WatermarkEvent},
+    Queue,
 };
 
 
-use super::prelude::{Prio, QueueFlag, Queueable};
+/// The identifier and element that `push_evicting` removed to make room.
+pub type Evicted<T> = (QueueId, T);
 
+/// The identifier `push_evicting` gave the new element, and the element evicted for it, if any.
+pub type EvictingPush<T> = (QueueId, Option<Evicted<T>>);
+
+/// Fill levels a `SizedQueue` reports crossings of, and whether the high one was the last crossed.
+struct Watermarks {
+    high: f32,
+    low: f32,
+    raised: bool,
+    /// Only ever reached through `&mut`, the mutex just keeps `SizedQueue` `Sync`.
+    callback: Mutex<Box<dyn FnMut(WatermarkEvent) + Send>>,
+}
 
 /// A SizedQueue is a queue that has a maximum element limit.
-/// If the queue is full, new elements added will be rejected, and an error will be returned.
+/// If the queue is full, new elements added will be rejected, and `QueueError::Full` is returned with the element.
+/// `push_evicting` can make room instead, following the queue's `OverflowPolicy`.
+/// Expired elements don't count towards the limit.
 ///
-/// You can expand this queue by calling the `expand` method.
-/// Once a queue is expanded, it cannot be shrunk back down.
+/// Admission bands can keep part of the queue free for urgent elements, see `set_admission_bands`.
+/// Watermarks report when the queue fills up or drains, see `set_watermarks`.
+///
+/// You can grow this queue by calling the `expand` method, or grow and shrink it with `resize`.
 ///
 /// # Similiarities with `Queue`
 /// #
@@ -19,135 +48,764 @@ use super::prelude::{Prio, QueueFlag, Queueable};
 /// The same mechanics as Queue are applied here to the Size version.
 ///
 /// Locks and priority rules are are still applied here, just a little head cap.
-pub struct SizedQueue<T>(BTreeMap<QueueFlag, T>, AtomicU32, u32)
+/// Both implement `PriorityQueue`, and this one `BoundedQueue` as well.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize, P: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>, P: serde::Deserialize<'de> + 'static"
+    ))
+)]
+pub struct SizedQueue<T, P = u32>
 where
-    T: Queueable + Clone;
+    T: Queueable,
+    P: Ord + Clone,
+{
+    queue: Queue<T, P>,
+    max_size: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    overflow: OverflowPolicy,
+    #[cfg_attr(feature = "serde", serde(default))]
+    bands: Vec<AdmissionBand<P>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    watermarks: Option<Watermarks>,
+}
 
 impl<T> SizedQueue<T>
 where
-    T: Queueable + Clone,
+    T: Queueable,
 {
     /// Creates a new LockableQueue.
     pub fn new(size: u32) -> Self {
-        Self(BTreeMap::new(), AtomicU32::new(0), size)
+        Self::with_max_size(size)
     }
 
-    /// Pushes a prioritized item to the queue.
-    /// Returns a unique identifier that can be used to track and retrieve this element in the queue
-    pub fn push(&mut self, item: Prio<T>) -> Result<u32, String> {
-        if self.0.len() as u32 >= self.2 {
-            return Err("Queue is full".to_string());
+    /// Sets the aging policy, or turns aging off with `None`.
+    ///
+    /// Elements only age for the time they wait after the policy is set.
+    pub fn set_aging(&mut self, policy: Option<AgingPolicy>) {
+        self.queue.set_aging(policy)
+    }
+}
+
+impl<T, P> SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// Creates a new SizedQueue holding at most `size` elements, for any priority type.
+    pub fn with_max_size(size: u32) -> Self {
+        Self::with_order(size, OrderPolicy::default())
+    }
+
+    /// Creates a new SizedQueue holding at most `size` elements, ordered by `order`.
+    pub fn with_order(size: u32, order: OrderPolicy) -> Self {
+        Self {
+            queue: Queue::with_order(order),
+            max_size: size,
+            overflow: OverflowPolicy::Reject,
+            bands: Vec::new(),
+            watermarks: None,
         }
+    }
 
-        let priority = item.priority().expect("Priority must be set");
-        let locked = item.locked;
+    /// Calls `callback` with `WatermarkEvent::High` when the queue fills to `high` percent,
+    /// and with `WatermarkEvent::Low` once it drains back to `low` percent. Replaces any watermarks set before.
+    ///
+    /// Each crossing is reported once: after a `High`, nothing is reported until the queue drains to `low`.
+    /// If the queue is already at `high`, `High` is reported right away.
+    /// The fill level is checked after every change made through this queue, so elements expiring
+    /// are only noticed on the next change.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low` is above `high`.
+    pub fn set_watermarks(&mut self, high: f32, low: f32, callback: impl FnMut(WatermarkEvent) + Send + 'static) {
+        assert!(low <= high, "low watermark {} is above high watermark {}", low, high);
+        self.watermarks = Some(Watermarks {
+            high,
+            low,
+            raised: false,
+            callback: Mutex::new(Box::new(callback)),
+        });
+        self.watch();
+    }
 
-        let new_identifier = self.1.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        let new_queue_flag = QueueFlag::new(priority, locked, new_identifier);
+    /// Like `set_watermarks`, but sends each crossing down a channel instead of calling back.
+    ///
+    /// Events are dropped once the receiver is.
+    pub fn watermark_channel(&mut self, high: f32, low: f32) -> Receiver<WatermarkEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.set_watermarks(high, low, move |event| {
+            let _ = sender.send(event);
+        });
+        receiver
+    }
 
-        self.0.insert(new_queue_flag, item.item);
+    /// Removes the watermarks, if any are set.
+    pub fn clear_watermarks(&mut self) {
+        self.watermarks = None;
+    }
 
-        return Ok(new_identifier);
+    /// Sets the admission bands every push is checked against, replacing any set before.
+    ///
+    /// An element is rejected with `QueueError::BandFull` if any band covering its priority is full.
+    /// Elements already in the queue stay, even if they are over the new limits.
+    pub fn set_admission_bands(&mut self, bands: Vec<AdmissionBand<P>>) {
+        self.bands = bands;
+        let direction = self.queue.order().direction;
+        self.bands.sort_by(|a, b| direction.cmp(&a.priority, &b.priority));
     }
 
-    // Naive linear search through the entire queue
-    pub fn retrieve_by_id_linear(&self, id: u32) -> Result<T, String> {
-        for flag in self.0.keys() {
-            if flag.identifier == id {
-                return Ok(self.0.get(flag).cloned().unwrap());
-            }
-        }
-        Err(format!("ID {} not found in the queue", id))
+    /// Returns how the queue orders its elements.
+    pub fn order(&self) -> OrderPolicy {
+        self.queue.order()
     }
 
-    pub fn retrieve_by_id(&self, id: u32) -> Result<T, String> {
-        // Check if the queue is empty
-        if self.0.is_empty() {
-            return Err("Queue is empty".to_string());
-        }
+    /// Makes the queue take its timestamps from `clock`, which is a `MonotonicClock` by default.
+    ///
+    /// Meant to be called right after creating the queue: elements already in it keep their timestamps.
+    /// Delayed and reserved elements still come due by the system's monotonic clock, see `Queue::push_at`.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.queue.clock = clock;
+        self
+    }
+
+    /// Returns whether `id` was handed out by this queue, whether or not its element is still in it.
+    /// See `Queue::owns` for how foreign identifiers are treated.
+    pub fn owns(&self, id: QueueId) -> bool {
+        self.queue.owns(id)
+    }
+
+    /// Returns the admission bands, most urgent first.
+    pub fn admission_bands(&self) -> &[AdmissionBand<P>] {
+        &self.bands
+    }
+
+    /// Sets what `push_evicting` does when the queue is full.
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow = policy;
+    }
 
-        // Get the smallest and largest IDs in the queue
-        let first_flag = self.0.keys().next().unwrap(); // Get the first (smallest) QueueFlag
-        let last_flag = self.0.keys().next_back().unwrap(); // Get the last (largest) QueueFlag
+    /// Returns what `push_evicting` does when the queue is full.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow
+    }
+
+    /// Sets the aging policy, or turns aging off with `None`.
+    /// `step` moves a priority up by the given number of levels.
+    ///
+    /// Elements only age for the time they wait after the policy is set.
+    pub fn set_aging_with(&mut self, policy: Option<AgingPolicy>, step: AgingStep<P>) {
+        self.queue.set_aging_with(policy, step)
+    }
 
-        let first_id = first_flag.identifier;
-        let last_id = last_flag.identifier;
+    /// Applies the aging policy, moving unlocked elements that have waited long enough up in the queue.
+    pub fn age(&mut self) {
+        self.queue.age()
+    }
 
-        // Check if the id is out of bounds
-        if id < first_id || id > last_id {
-            return Err(format!(
-                "ID {} is not in the range of current queue ({} to {})",
-                id, first_id, last_id
-            ));
+    /// Pushes a prioritized item to the queue.
+    /// Returns a unique identifier that can be used to track and retrieve this element in the queue
+    pub fn push(&mut self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>> {
+        if self.queue.size() as u32 >= self.max_size {
+            return Err(QueueError::Full(item));
         }
 
-        // Determine whether to start from the front or the back based on proximity
-        if id - first_id <= last_id - id {
-            // Start from the beginning and search forwards
-            for flag in self.0.keys() {
-                if flag.identifier == id {
-                    return Ok(self.0.get(flag).cloned().unwrap());
-                }
-            }
-        } else {
-            // Start from the end and search backwards
-            for flag in self.0.keys().rev() {
-                if flag.identifier == id {
-                    return Ok(self.0.get(flag).cloned().unwrap());
-                }
+        let item = self.admit(item)?;
+        let id = self.queue.push(item)?;
+        self.watch();
+        Ok(id)
+    }
+
+    /// Pushes a prioritized item to the queue, making room for it under the overflow policy if the queue is full.
+    /// Returns the identifier of the new element, along with the identifier and element that were evicted for it.
+    ///
+    /// If the policy finds nothing to evict, the item is rejected with `QueueError::Full`.
+    pub fn push_evicting(&mut self, item: Prio<T, P>) -> Result<EvictingPush<T>, QueueError<T, P>> {
+        let Some(priority) = item.priority() else {
+            return Err(QueueError::MissingPriority(item));
+        };
+        let item = self.admit(item)?;
+        if (self.queue.size() as u32) < self.max_size {
+            let id = self.queue.push(item)?;
+            self.watch();
+            return Ok((id, None));
+        }
+
+        self.queue.refresh();
+        let direction = self.queue.order().direction;
+        let victim = {
+            let unlocked = self.queue.visible().map(|(flag, _)| flag.clone()).filter(|flag| flag.locked == LockStatus::Unlocked);
+            match self.overflow {
+                OverflowPolicy::Reject => None,
+                OverflowPolicy::EvictLowest => unlocked.max().filter(|flag| direction.cmp(&priority, &flag.priority).is_lt()),
+                OverflowPolicy::DropOldest => unlocked.min_by_key(|flag| (flag.timestamp, flag.identifier)),
+                OverflowPolicy::DropNewest => unlocked.max_by_key(|flag| (flag.timestamp, flag.identifier)),
             }
+        };
+        let Some(victim) = victim.map(|flag| flag.identifier) else {
+            return Err(QueueError::Full(item));
+        };
+
+        let evicted = self.queue.remove(victim).expect("visible element missing from the queue");
+        self.queue.forget(victim, Instant::now());
+        let id = self.queue.push(item)?;
+        self.watch();
+        Ok((id, Some((victim, evicted))))
+    }
+
+    /// Pushes a non-prioritized item to the queue, behind everything already in it.
+    /// No item pushed through this function will be locked
+    pub fn push_non_prio(&mut self, item: T) -> Result<QueueId, QueueError<T, P>>
+    where
+        P: From<u32>,
+    {
+        let priority = self.queue.back_priority();
+        self.push(Prio::new(item, Some(priority), LockStatus::Unlocked))
+    }
+
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `delay` has passed.
+    /// Delayed elements take up room in the queue while they wait.
+    pub fn push_delayed(&mut self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>> {
+        self.push_at(item, Instant::now() + delay)
+    }
+
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `when`.
+    /// Delayed elements take up room in the queue while they wait.
+    pub fn push_at(&mut self, item: Prio<T, P>, when: impl Deadline) -> Result<QueueId, QueueError<T, P>> {
+        if self.queue.size() as u32 >= self.max_size {
+            return Err(QueueError::Full(item));
         }
 
-        Err(format!("ID {} not found in the queue", id))
+        let item = self.admit(item)?;
+        let id = self.queue.push_at(item, when)?;
+        self.watch();
+        Ok(id)
+    }
+
+    /// Returns when the earliest delayed element becomes due, or `None` if nothing is delayed.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.queue.next_due()
     }
 
     /// Pops the next item in the queue.
     pub fn pop(&mut self) -> Option<T> {
-        let lowest_flag = self.0.keys().next().cloned();
+        let result = self.queue.pop();
+        self.watch();
+        result
+    }
 
-        if let Some(flag) = lowest_flag {
-            let value = self.0.remove(&flag).unwrap();
-            return Some(value);
+    /// Settles a lease by removing its element from the queue for good.
+    pub fn ack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        let result = self.queue.ack(lease);
+        self.watch();
+        result
+    }
+
+    /// Settles a lease by putting its element back into the queue at its old place.
+    pub fn nack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        let result = self.queue.nack(lease);
+        self.watch();
+        result
+    }
+
+    /// Returns how many times processing an element has been reported as failed through `nack`.
+    pub fn failures(&self, id: QueueId) -> u32 {
+        self.queue.failures(id)
+    }
+
+    /// Attaches a dead-letter queue, which elements move to once they have failed `max_failures` times.
+    /// Dead-lettered elements don't count towards the limit.
+    pub fn set_dead_letter(&mut self, max_failures: u32) {
+        self.queue.set_dead_letter(max_failures)
+    }
+
+    /// Returns the dead-letter queue, if one is attached.
+    pub fn dead_letters(&self) -> Option<&Queue<T, P>> {
+        self.queue.dead_letters()
+    }
+
+    /// Moves an element out of the dead-letter queue and back into this one, with its failures reset.
+    ///
+    /// If the queue is full, the element stays dead-lettered and `QueueError::NoRoom` is returned.
+    pub fn requeue_dead_letter(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        if self.queue.size() as u32 >= self.max_size {
+            if !self.queue.owns(id) {
+                return Err(QueueError::ForeignId(id));
+            }
+            let dead = self.queue.dead_letters().ok_or(QueueError::NotFound(id))?;
+            if !dead.index.contains_key(&id) {
+                return Err(QueueError::NotFound(id));
+            }
+            return Err(QueueError::NoRoom(id));
         }
-        None
+
+        let result = self.queue.requeue_dead_letter(id);
+        self.watch();
+        result
     }
-    
+
+    /// Removes an element from the dead-letter queue for good.
+    pub fn drop_dead_letter(&mut self, id: QueueId) -> Option<T> {
+        self.queue.drop_dead_letter(id)
+    }
+
+    /// Pops an element based on the id
+    pub fn pop_by_id(&mut self, id: QueueId) -> Option<T> {
+        let result = self.queue.pop_by_id(id);
+        self.watch();
+        result
+    }
+
+    /// Removes every expired element from the queue.
+    /// Returns the identifiers and elements that were dropped, in the order they expired.
+    pub fn purge_expired(&mut self) -> Vec<(QueueId, T)> {
+        let result = self.queue.purge_expired();
+        self.watch();
+        result
+    }
+
+    /// Changes the priority of an element, keeping its identifier.
+    /// The element is re-sorted under its new priority.
+    pub fn reprioritize(&mut self, id: QueueId, priority: P, position: FifoPosition) -> Result<(), QueueError<T, P>> {
+        self.queue.reprioritize(id, priority, position)
+    }
+
+    /// Locks an element in place, keeping its identifier and position.
+    pub fn lock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.queue.lock(id)
+    }
+
+    /// Unlocks an element, keeping its identifier and position.
+    pub fn unlock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.queue.unlock(id)
+    }
+
     /// Peeks the next item in the queue.
     /// Returns a reference to the next item in the queue.
     /// Returns the identifier of the item.
-    pub fn peek(&self) -> Option<(&T, u32)> {
-        let lowest_flag = self.0.keys().next().cloned();
-        let element = self.0.get(&lowest_flag.unwrap());
-        let identifier = lowest_flag.unwrap().identifier;
-        return element.map(|e| (e, identifier));
+    pub fn peek(&self) -> Option<(&T, QueueId)> {
+        self.queue.peek()
     }
 
-    /// Returns all elements in the queue as a Vec.
-    pub fn get_elements(&self) -> Vec<T> {
-        self.0.values().cloned().collect()
+    /// Iterates over the elements in the queue in the order they would be popped, without cloning them.
+    pub fn iter(&self) -> Iter<'_, T, P> {
+        self.queue.iter()
+    }
+
+    /// Iterates over the elements in the queue in the order they would be popped, allowing them to be changed.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, P> {
+        self.queue.iter_mut()
+    }
+
+    /// Removes every element `pop` can currently reach, and iterates over them in the order they would have been popped.
+    pub fn drain(&mut self) -> Drain<T, P> {
+        let result = self.queue.drain();
+        self.watch();
+        result
     }
 
     /// Returns the size of the queue
     pub fn size(&self) -> usize {
-        self.0.len()
+        self.queue.size()
     }
 
     /// Returns the maximum size of the queue
     pub fn max_size(&self) -> u32 {
-        self.2
+        self.max_size
     }
     
     /// Increases the maximum size of the queue
     ///
-    /// Once a queue is expanded, it cannot be shrunk back down.
-    pub fn expand(&mut self, size: usize) {
-        self.2 += size as u32;
+    /// Fails with `QueueError::CapacityOverflow`, leaving the size as it was, if the new size does not fit in a `u32`.
+    pub fn expand(&mut self, size: usize) -> Result<(), QueueError<T, P>> {
+        self.max_size = u32::try_from(size)
+            .ok()
+            .and_then(|size| self.max_size.checked_add(size))
+            .ok_or(QueueError::CapacityOverflow)?;
+        self.watch();
+        Ok(())
+    }
+
+    /// Sets the maximum size of the queue, growing or shrinking it.
+    /// Returns the elements removed to fit under the new limit, which only `ShrinkPolicy::Truncate` does.
+    ///
+    /// If the queue holds more than `max_size` elements, `policy` decides what happens.
+    pub fn resize(&mut self, max_size: u32, policy: ShrinkPolicy) -> Result<Vec<Evicted<T>>, QueueError<T, P>> {
+        let size = self.queue.size();
+        let excess = size.saturating_sub(max_size as usize);
+        if excess == 0 || policy == ShrinkPolicy::AllowOverflow {
+            self.max_size = max_size;
+            self.watch();
+            return Ok(Vec::new());
+        }
+        if policy == ShrinkPolicy::Refuse {
+            return Err(QueueError::Overfull(size));
+        }
+
+        self.queue.refresh();
+        let mut unlocked: Vec<QueueFlag<P>> = self
+            .queue
+            .visible()
+            .map(|(flag, _)| flag.clone())
+            .filter(|flag| flag.locked == LockStatus::Unlocked)
+            .collect();
+        if unlocked.len() < excess {
+            return Err(QueueError::Overfull(size));
+        }
+
+        unlocked.sort_unstable_by(|a, b| b.cmp(a));
+        let now = Instant::now();
+        let truncated = unlocked
+            .into_iter()
+            .take(excess)
+            .map(|flag| {
+                let item = self.queue.remove(flag.identifier).expect("visible element missing from the queue");
+                self.queue.forget(flag.identifier, now);
+                (flag.identifier, item)
+            })
+            .collect();
+
+        self.max_size = max_size;
+        self.watch();
+        Ok(truncated)
     }
     
+    /// Reports a watermark crossing, if the last change made one.
+    fn watch(&mut self) {
+        let percent = self.percentage_full();
+        let Some(watermarks) = self.watermarks.as_mut() else {
+            return;
+        };
+        let callback = watermarks.callback.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if !watermarks.raised && percent >= watermarks.high {
+            watermarks.raised = true;
+            callback(WatermarkEvent::High(percent));
+        } else if watermarks.raised && percent <= watermarks.low {
+            watermarks.raised = false;
+            callback(WatermarkEvent::Low(percent));
+        }
+    }
+
+    /// Checks an item against the admission bands covering its priority.
+    fn admit(&self, item: Prio<T, P>) -> Result<Prio<T, P>, QueueError<T, P>> {
+        let Some(priority) = item.priority() else {
+            return Ok(item);
+        };
+        let direction = self.queue.order().direction;
+        let mut covering = self.bands.iter().filter(|band| band.covers(&priority, direction)).peekable();
+        if covering.peek().is_none() {
+            return Ok(item);
+        }
+
+        let now = Instant::now();
+        for band in covering {
+            let held = self.queue.count_behind(&band.priority, now);
+            if held as u32 >= band.slots(self.max_size) {
+                return Err(QueueError::BandFull(band.clone(), item));
+            }
+        }
+        Ok(item)
+    }
+
     /// Returns a percentage of the queue's size
+    /// Goes over 100 while a queue resized with `ShrinkPolicy::AllowOverflow` drains.
     pub fn percentage_full(&self) -> f32 {
-        (self.0.len() as f32 / self.2 as f32) * 100.0
+        self.queue.size() as f32 * 100.0 / self.max_size as f32
     }
 
 
 }
+
+impl<T, P> SizedQueue<T, P>
+where
+    T: Queueable + Clone,
+    P: Ord + Clone,
+{
+    // Naive linear search through the entire queue
+    pub fn retrieve_by_id_linear(&self, id: QueueId) -> Result<T, QueueError<T, P>> {
+        self.queue.retrieve_by_id_linear(id)
+    }
+
+    /// Returns a copy of the element with the given identifier.
+    pub fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T, P>> {
+        self.queue.retrieve_by_id(id)
+    }
+
+    /// Hands out the next item in the queue without removing it.
+    /// Reserved elements keep taking up room until they are acknowledged.
+    pub fn reserve(&mut self, visibility_timeout: Duration) -> Option<(T, Lease)> {
+        self.queue.reserve(visibility_timeout)
+    }
+
+    /// Returns all elements in the queue as a Vec.
+    pub fn get_elements(&self) -> Vec<T> {
+        self.queue.get_elements()
+    }
+
+    /// Returns a copy of the entire queue, including delayed and expired elements.
+    pub fn all(&self) -> BTreeMap<QueueFlag<P>, T> {
+        self.queue.all()
+    }
+}
+
+impl<T, P> PriorityQueue<T, P> for SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn order(&self) -> OrderPolicy {
+        self.order()
+    }
+
+    fn owns(&self, id: QueueId) -> bool {
+        self.owns(id)
+    }
+
+    fn set_aging_with(&mut self, policy: Option<AgingPolicy>, step: AgingStep<P>) {
+        self.set_aging_with(policy, step)
+    }
+
+    fn age(&mut self) {
+        self.age()
+    }
+
+    fn push(&mut self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>> {
+        self.push(item)
+    }
+
+    fn push_non_prio(&mut self, item: T) -> Result<QueueId, QueueError<T, P>>
+    where
+        P: From<u32>,
+    {
+        self.push_non_prio(item)
+    }
+
+    fn push_delayed(&mut self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>> {
+        self.push_delayed(item, delay)
+    }
+
+    fn push_at<D>(&mut self, item: Prio<T, P>, when: D) -> Result<QueueId, QueueError<T, P>>
+    where
+        D: Deadline,
+    {
+        self.push_at(item, when)
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.next_due()
+    }
+
+    fn retrieve_by_id_linear(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone,
+    {
+        self.retrieve_by_id_linear(id)
+    }
+
+    fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone,
+    {
+        self.retrieve_by_id(id)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        self.pop()
+    }
+
+    fn reserve(&mut self, visibility_timeout: Duration) -> Option<(T, Lease)>
+    where
+        T: Clone,
+    {
+        self.reserve(visibility_timeout)
+    }
+
+    fn ack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        self.ack(lease)
+    }
+
+    fn nack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>> {
+        self.nack(lease)
+    }
+
+    fn failures(&self, id: QueueId) -> u32 {
+        self.failures(id)
+    }
+
+    fn set_dead_letter(&mut self, max_failures: u32) {
+        self.set_dead_letter(max_failures)
+    }
+
+    fn dead_letters(&self) -> Option<&dyn PriorityQueue<T, P>> {
+        self.dead_letters().map(|dead| dead as &dyn PriorityQueue<T, P>)
+    }
+
+    fn requeue_dead_letter(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.requeue_dead_letter(id)
+    }
+
+    fn drop_dead_letter(&mut self, id: QueueId) -> Option<T> {
+        self.drop_dead_letter(id)
+    }
+
+    fn pop_by_id(&mut self, id: QueueId) -> Option<T> {
+        self.pop_by_id(id)
+    }
+
+    fn purge_expired(&mut self) -> Vec<(QueueId, T)> {
+        self.purge_expired()
+    }
+
+    fn reprioritize(&mut self, id: QueueId, priority: P, position: FifoPosition) -> Result<(), QueueError<T, P>> {
+        self.reprioritize(id, priority, position)
+    }
+
+    fn lock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.lock(id)
+    }
+
+    fn unlock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>> {
+        self.unlock(id)
+    }
+
+    fn peek(&self) -> Option<(&T, QueueId)> {
+        self.peek()
+    }
+
+    fn get_elements(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.get_elements()
+    }
+
+    fn all(&self) -> BTreeMap<QueueFlag<P>, T>
+    where
+        T: Clone,
+    {
+        self.all()
+    }
+
+    fn size(&self) -> usize {
+        self.size()
+    }
+}
+
+impl<T, P> BoundedQueue<T, P> for SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn max_size(&self) -> u32 {
+        self.max_size()
+    }
+
+    fn expand(&mut self, size: usize) -> Result<(), QueueError<T, P>> {
+        self.expand(size)
+    }
+
+    fn resize(&mut self, max_size: u32, policy: ShrinkPolicy) -> Result<Vec<Evicted<T>>, QueueError<T, P>> {
+        self.resize(max_size, policy)
+    }
+
+    fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy()
+    }
+
+    fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.set_overflow_policy(policy)
+    }
+
+    fn push_evicting(&mut self, item: Prio<T, P>) -> Result<EvictingPush<T>, QueueError<T, P>> {
+        self.push_evicting(item)
+    }
+
+    fn percentage_full(&self) -> f32 {
+        self.percentage_full()
+    }
+}
+
+impl<T, P> IntoIterator for SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (QueueFlag<P>, T);
+    type IntoIter = IntoIter<T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.queue.into_iter()
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (&'a QueueFlag<P>, &'a T);
+    type IntoIter = Iter<'a, T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, P> IntoIterator for &'a mut SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    type Item = (&'a QueueFlag<P>, &'a mut T);
+    type IntoIter = IterMut<'a, T, P>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Pushes every item in turn, following the same rules as `push`.
+///
+/// # Panics
+///
+/// Panics if an item has no priority set, or is rejected because the queue is full.
+impl<T, P> Extend<Prio<T, P>> for SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn extend<I: IntoIterator<Item = Prio<T, P>>>(&mut self, iter: I) {
+        for item in iter {
+            match self.push(item) {
+                Ok(_) => {}
+                Err(QueueError::MissingPriority(_)) => panic!("priority must be set"),
+                Err(QueueError::Full(_)) => panic!("queue is full"),
+                Err(_) => panic!("queue rejected the item"),
+            }
+        }
+    }
+}
+
+/// Creates a SizedQueue just big enough to hold every item.
+///
+/// # Panics
+///
+/// Panics if an item has no priority set, or if there are more items than fit in a `u32`.
+impl<T, P> FromIterator<Prio<T, P>> for SizedQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = Prio<T, P>>>(iter: I) -> Self {
+        let items: Vec<_> = iter.into_iter().collect();
+        let size = u32::try_from(items.len()).expect("too many items for a SizedQueue");
+
+        let mut queue = SizedQueue::with_max_size(size);
+        queue.extend(items);
+        queue
+    }
+}
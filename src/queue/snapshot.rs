@@ -0,0 +1,146 @@
+//! Serde support for `Queue`.
+//!
+//! A queue is saved as a snapshot of its elements and everything it tracks about them, including the
+//! identifier counter, so a restored queue keeps its order and hands out identifiers where it left off.
+//!
+//! Points in time are saved relative to when the snapshot was taken: an element delayed by a minute
+//! is still delayed by a minute once restored. Leases are not saved. Reserved elements become
+//! visible again when their visibility timeout would have run out.
+//!
+//! The aging policy is saved, but its step can't be. Restored `u32` queues age as before;
+//! queues of other priority types need `set_aging_with` called again.
+
+use std::{
+    any::Any,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    prelude::{AgingPolicy, AgingStep, Direction, OrderPolicy, QueueFlag, QueueId, Queueable},
+    Queue,
+};
+
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot<T, P> {
+    origin: u64,
+    next_id: u64,
+    entries: Vec<(QueueFlag<P>, T)>,
+    /// Delayed and reserved elements, with how long until they are due.
+    scheduled: Vec<(Duration, QueueFlag<P>, T)>,
+    /// How long until each element with a time-to-live expires.
+    expiry: Vec<(QueueId, Duration)>,
+    failures: Vec<(QueueId, u32)>,
+    aging: Option<AgingPolicy>,
+    #[serde(default)]
+    order: OrderPolicy,
+    dead_letter: Option<(u32, Box<Snapshot<T, P>>)>,
+}
+
+impl<'a, T, P> Snapshot<&'a T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    fn of(queue: &'a Queue<T, P>) -> Self {
+        let now = Instant::now();
+        Self {
+            origin: queue.origin,
+            next_id: queue.next_id.load(Ordering::Relaxed),
+            entries: queue.entries.iter().map(|(flag, item)| (flag.clone(), item)).collect(),
+            scheduled: queue
+                .scheduled
+                .iter_due()
+                .map(|(due, flag, item)| (due.saturating_duration_since(now), flag.clone(), item))
+                .collect(),
+            expiry: queue
+                .expiry
+                .iter()
+                .map(|(id, at)| (id, at.saturating_duration_since(now)))
+                .collect(),
+            failures: queue.failures.iter().map(|(id, count)| (*id, *count)).collect(),
+            aging: queue.aging.map(|(policy, _)| policy),
+            order: queue.order,
+            dead_letter: queue
+                .dead_letter
+                .as_ref()
+                .map(|(max_failures, dead)| (*max_failures, Box::new(Snapshot::of(dead)))),
+        }
+    }
+}
+
+impl<T, P> Snapshot<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone + 'static,
+{
+    fn restore(self) -> Queue<T, P> {
+        let now = Instant::now();
+        let mut queue = Queue {
+            origin: self.origin,
+            next_id: AtomicU64::new(self.next_id),
+            ..Queue::with_order(self.order)
+        };
+
+        for (flag, item) in self.entries {
+            queue.insert(flag, item);
+        }
+        for (remaining, flag, item) in self.scheduled {
+            queue.tally(&flag.priority);
+            queue.scheduled.insert(now + remaining, flag, item);
+        }
+        for (id, remaining) in self.expiry {
+            queue.expiry.insert(id, now + remaining);
+        }
+        queue.failures = self.failures.into_iter().collect();
+        if let Some(step) = saturating_step::<P>(self.order.direction) {
+            queue.set_aging_with(self.aging, step);
+        }
+        queue.dead_letter = self
+            .dead_letter
+            .map(|(max_failures, dead)| (max_failures, Box::new(dead.restore())));
+
+        queue
+    }
+}
+
+/// The step `Queue::set_aging` uses, if `P` is `u32`.
+fn saturating_step<P>(direction: Direction) -> Option<AgingStep<P>>
+where
+    P: 'static,
+{
+    let step: AgingStep<u32> = match direction {
+        Direction::MinFirst => u32::saturating_sub,
+        Direction::MaxFirst => u32::saturating_add,
+    };
+    (&step as &dyn Any).downcast_ref::<AgingStep<P>>().copied()
+}
+
+impl<T, P> Serialize for Queue<T, P>
+where
+    T: Queueable + Serialize,
+    P: Ord + Clone + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Snapshot::of(self).serialize(serializer)
+    }
+}
+
+impl<'de, T, P> Deserialize<'de> for Queue<T, P>
+where
+    T: Queueable + Deserialize<'de>,
+    P: Ord + Clone + Deserialize<'de> + 'static,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Snapshot::deserialize(deserializer).map(Snapshot::restore)
+    }
+}
@@ -0,0 +1,376 @@
+//! A `Queue` that moves its lowest-priority elements to disk once it grows too large.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    error::QueueError,
+    prelude::{Prio, QueueFlag, QueueId, Queueable},
+    Queue,
+};
+
+
+/// How much of a `SpillQueue` is kept in memory.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SpillThreshold {
+    /// Spill once more than this many elements are held in memory.
+    Entries(usize),
+    /// Spill once the elements held in memory take up more than this many bytes,
+    /// estimated from the size of each element once serialized.
+    Bytes(usize),
+}
+
+impl SpillThreshold {
+    fn limit(&self) -> usize {
+        match self {
+            SpillThreshold::Entries(limit) | SpillThreshold::Bytes(limit) => *limit,
+        }
+    }
+}
+
+/// Errors returned by `SpillQueue` operations.
+#[derive(Debug)]
+pub enum SpillError<T>
+where
+    T: Queueable,
+{
+    /// The operation was rejected by the queue.
+    Queue(QueueError<T>),
+    /// Reading or writing a segment file failed.
+    Io(io::Error),
+}
+
+impl<T> fmt::Display for SpillError<T>
+where
+    T: Queueable,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpillError::Queue(err) => err.fmt(f),
+            SpillError::Io(err) => write!(f, "spill queue I/O failed: {}", err),
+        }
+    }
+}
+
+impl<T> Error for SpillError<T>
+where
+    T: Queueable + fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpillError::Queue(_) => None,
+            SpillError::Io(err) => Some(err),
+        }
+    }
+}
+
+impl<T> From<QueueError<T>> for SpillError<T>
+where
+    T: Queueable,
+{
+    fn from(err: QueueError<T>) -> Self {
+        SpillError::Queue(err)
+    }
+}
+
+impl<T> From<io::Error> for SpillError<T>
+where
+    T: Queueable,
+{
+    fn from(err: io::Error) -> Self {
+        SpillError::Io(err)
+    }
+}
+
+/// One spill, written to disk in the order it will be popped.
+///
+/// A segment is removed as soon as its last element is taken, so its head is always there.
+struct Segment<T> {
+    path: PathBuf,
+    reader: BufReader<File>,
+    /// Where the element after the head starts in the file.
+    offset: u64,
+    /// The next element of the segment, already read back in.
+    head: (QueueFlag, T),
+}
+
+impl<T> Segment<T>
+where
+    T: DeserializeOwned,
+{
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(&path)?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        Ok(Self {
+            head: serde_json::from_str(&line)?,
+            offset: line.len() as u64,
+            reader,
+            path,
+        })
+    }
+
+    /// Reads the element after the head, or `None` once the segment is drained.
+    /// On failure the segment is left as it was, so reading can be retried.
+    fn read_next(&mut self) -> io::Result<Option<(QueueFlag, T)>> {
+        let mut line = String::new();
+        let next = self.reader.read_line(&mut line).and_then(|read| match read {
+            0 => Ok(None),
+            _ => Ok(Some(serde_json::from_str(&line)?)),
+        });
+        match next {
+            Ok(_) => self.offset += line.len() as u64,
+            Err(_) => {
+                self.reader.seek(SeekFrom::Start(self.offset))?;
+            }
+        }
+        next
+    }
+}
+
+/// A SpillQueue is a `Queue` that keeps only its head in memory.
+///
+/// Once the elements in memory pass the threshold, the lowest-priority half of them is written
+/// to a new segment file in a scratch directory, already sorted. Popping merges the elements in
+/// memory with the first element of each segment, so `pop` and `peek` behave exactly like they
+/// would on a `Queue` holding everything, and nothing is ever read or written twice.
+///
+/// Besides the threshold, memory holds one element per segment. The segment files are scratch space
+/// only, and are removed once drained or when the queue is dropped.
+pub struct SpillQueue<T>
+where
+    T: Queueable,
+{
+    memory: Queue<T>,
+    /// The estimated weight of each element in memory, in the threshold's unit.
+    weights: HashMap<QueueId, usize>,
+    weight: usize,
+    threshold: SpillThreshold,
+    dir: PathBuf,
+    segments: Vec<Segment<T>>,
+    next_segment: u64,
+    spilled: usize,
+}
+
+impl<T> SpillQueue<T>
+where
+    T: Queueable + Serialize + DeserializeOwned,
+{
+    /// Creates a new SpillQueue that writes its segment files to `dir`, creating the directory if needed.
+    pub fn new(dir: impl AsRef<Path>, threshold: SpillThreshold) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            memory: Queue::new(),
+            weights: HashMap::new(),
+            weight: 0,
+            threshold,
+            dir,
+            segments: Vec::new(),
+            next_segment: 0,
+            spilled: 0,
+        })
+    }
+
+    /// Pushes a prioritized item to the queue.
+    /// Returns a unique identifier that can be used to track this element in the queue
+    pub fn push(&mut self, item: Prio<T>) -> Result<QueueId, SpillError<T>> {
+        let Some(priority) = item.priority() else {
+            return Err(QueueError::MissingPriority(item).into());
+        };
+        let Some(id) = self.memory.next_identifier() else {
+            return Err(QueueError::IdsExhausted(item).into());
+        };
+        let flag = self.memory.new_flag(priority, item.locked, id);
+        let ttl = item.ttl();
+
+        self.keep(flag, item.item)?;
+        self.memory.track_ttl(id, ttl);
+        if self.weight > self.threshold.limit() {
+            self.spill()?;
+        }
+
+        Ok(flag.identifier)
+    }
+
+    /// Pops the next item in the queue, whether it is held in memory or on disk.
+    /// Expired elements are dropped on the way, like `Queue::pop` does.
+    pub fn pop(&mut self) -> Result<Option<T>, SpillError<T>> {
+        let now = Instant::now();
+        while let Some((id, item)) = self.take_head()? {
+            if !self.memory.forget(id, now) {
+                return Ok(Some(item));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Peeks the next item in the queue.
+    /// Returns a reference to the next item in the queue.
+    ///
+    /// Expired elements in front of it are dropped, which can mean reading the next element of a segment.
+    pub fn peek(&mut self) -> Result<Option<(&T, QueueId)>, SpillError<T>> {
+        let now = Instant::now();
+        while let Some(id) = self.head_id().filter(|&id| self.memory.expiry.is_expired(id, now)) {
+            self.take_head()?;
+            self.memory.forget(id, now);
+        }
+        Ok(self.head())
+    }
+
+    fn head(&self) -> Option<(&T, QueueId)> {
+        let memory = self.memory.entries.first_key_value();
+        let disk = self
+            .next_segment()
+            .map(|segment| &self.segments[segment].head)
+            .map(|(flag, item)| (flag, item));
+
+        let (flag, item) = match (memory, disk) {
+            (Some(memory), Some(disk)) if disk.0 < memory.0 => disk,
+            (memory, disk) => memory.or(disk)?,
+        };
+        Some((item, flag.identifier))
+    }
+
+    /// Returns the size of the queue, on disk and in memory, leaving out expired elements.
+    pub fn size(&self) -> usize {
+        self.memory.entries.len() + self.spilled - self.memory.expiry.count_expired(Instant::now())
+    }
+
+    /// Returns how many elements are on disk.
+    pub fn spilled(&self) -> usize {
+        self.spilled
+    }
+
+    fn keep(&mut self, flag: QueueFlag, item: T) -> io::Result<()> {
+        let weight = match self.threshold {
+            SpillThreshold::Entries(_) => 1,
+            SpillThreshold::Bytes(_) => serde_json::to_vec(&item)?.len() + mem::size_of::<QueueFlag>(),
+        };
+        self.weights.insert(flag.identifier, weight);
+        self.weight += weight;
+        self.memory.insert(flag, item);
+        Ok(())
+    }
+
+    fn head_id(&self) -> Option<QueueId> {
+        self.head().map(|(_, id)| id)
+    }
+
+    /// Takes the next element out of memory or a segment, whether it has expired or not.
+    fn take_head(&mut self) -> Result<Option<(QueueId, T)>, SpillError<T>> {
+        let memory = self.memory.entries.first_key_value().map(|(flag, _)| *flag);
+        let segment = match (memory, self.next_segment()) {
+            (None, None) => return Ok(None),
+            (Some(flag), Some(segment)) if self.head_flag(segment) < flag => segment,
+            (None, Some(segment)) => segment,
+            (Some(_), _) => {
+                let (flag, item) = self.memory.entries.pop_first().expect("checked above");
+                self.memory.untrack(&flag);
+                self.weight -= self.weights.remove(&flag.identifier).unwrap_or(0);
+                return Ok(Some((flag.identifier, item)));
+            }
+        };
+        let id = self.head_flag(segment).identifier;
+        Ok(self.pop_segment(segment)?.map(|item| (id, item)))
+    }
+
+    /// Returns the segment whose head is popped first.
+    fn next_segment(&self) -> Option<usize> {
+        (0..self.segments.len()).min_by_key(|&segment| self.head_flag(segment))
+    }
+
+    fn head_flag(&self, segment: usize) -> QueueFlag {
+        self.segments[segment].head.0
+    }
+
+    /// Takes the head of a segment, reading the next one in and removing the segment once drained.
+    /// Nothing is taken if reading or removing the segment fails.
+    fn pop_segment(&mut self, index: usize) -> Result<Option<T>, SpillError<T>> {
+        let segment = &mut self.segments[index];
+        let item = match segment.read_next()? {
+            Some(next) => mem::replace(&mut segment.head, next).1,
+            None => {
+                fs::remove_file(&segment.path)?;
+                self.segments.swap_remove(index).head.1
+            }
+        };
+        self.spilled -= 1;
+        Ok(Some(item))
+    }
+
+    /// Moves the tail of memory to a new segment, until memory is down to half the threshold.
+    /// The elements stay in memory if the segment can't be written.
+    fn spill(&mut self) -> io::Result<()> {
+        let mut weight = self.weight;
+        let tail: Vec<QueueFlag> = self
+            .memory
+            .entries
+            .keys()
+            .rev()
+            .take(self.memory.entries.len() - 1)
+            .take_while(|flag| {
+                let spill = weight > self.threshold.limit() / 2;
+                weight -= self.weights.get(&flag.identifier).copied().unwrap_or(0);
+                spill
+            })
+            .copied()
+            .collect();
+        if tail.is_empty() {
+            return Ok(());
+        }
+
+        let path = self.dir.join(format!("segment-{}.jsonl", self.next_segment));
+        self.next_segment += 1;
+        let segment = self.write_segment(&path, &tail).and_then(|()| Segment::open(path.clone()));
+        let segment = match segment {
+            Ok(segment) => segment,
+            Err(err) => {
+                let _ = fs::remove_file(&path);
+                return Err(err);
+            }
+        };
+
+        for flag in &tail {
+            self.memory.entries.remove(flag);
+            self.memory.untrack(flag);
+            self.weight -= self.weights.remove(&flag.identifier).unwrap_or(0);
+        }
+        self.spilled += tail.len();
+        self.segments.push(segment);
+        Ok(())
+    }
+
+    /// Writes the elements under `tail`, lowest priority first, to a segment file in popping order.
+    fn write_segment(&self, path: &Path, tail: &[QueueFlag]) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+        let mut writer = BufWriter::new(file);
+        for flag in tail.iter().rev() {
+            serde_json::to_writer(&mut writer, &(flag, &self.memory.entries[flag]))?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+}
+
+impl<T> Drop for SpillQueue<T>
+where
+    T: Queueable,
+{
+    fn drop(&mut self) {
+        for segment in mem::take(&mut self.segments) {
+            let _ = fs::remove_file(&segment.path);
+        }
+    }
+}
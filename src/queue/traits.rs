@@ -0,0 +1,171 @@
+//! Traits for writing code that accepts any of the crate's queues.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::{
+    error::QueueError,
+    prelude::{AgingPolicy, AgingStep, Deadline, FifoPosition, Lease, OrderPolicy, OverflowPolicy, Prio, QueueFlag, QueueId, Queueable, ShrinkPolicy},
+    sized::{EvictingPush, Evicted},
+};
+
+
+/// The API shared by `Queue` and `SizedQueue`.
+///
+/// Every method behaves exactly like the inherent method of the same name.
+/// The methods that hand out copies of elements need them to be `Clone`.
+/// Bounded queues reject pushes once they are full, see `BoundedQueue`.
+///
+/// The trait can be used as `dyn PriorityQueue<T, P>`. `push_at` is generic over its deadline,
+/// so it is only available on concrete types, `push_delayed` covers trait objects.
+pub trait PriorityQueue<T, P = u32>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// Returns how the queue orders its elements.
+    fn order(&self) -> OrderPolicy;
+
+    /// Returns whether `id` was handed out by this queue.
+    /// Methods that return an `Option` treat foreign identifiers like missing ones, see `Queue::owns`.
+    fn owns(&self, id: QueueId) -> bool;
+
+    /// Sets the aging policy, or turns aging off with `None`.
+    /// `step` moves a priority up by the given number of levels.
+    fn set_aging_with(&mut self, policy: Option<AgingPolicy>, step: AgingStep<P>);
+
+    /// Applies the aging policy.
+    fn age(&mut self);
+
+    /// Pushes a prioritized item to the queue.
+    fn push(&mut self, item: Prio<T, P>) -> Result<QueueId, QueueError<T, P>>;
+
+    /// Pushes an unlocked item behind everything in the queue.
+    fn push_non_prio(&mut self, item: T) -> Result<QueueId, QueueError<T, P>>
+    where
+        P: From<u32>;
+
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `delay` has passed.
+    fn push_delayed(&mut self, item: Prio<T, P>, delay: Duration) -> Result<QueueId, QueueError<T, P>>;
+
+    /// Pushes a prioritized item that stays hidden from `pop` and `peek` until `when`.
+    fn push_at<D>(&mut self, item: Prio<T, P>, when: D) -> Result<QueueId, QueueError<T, P>>
+    where
+        D: Deadline,
+        Self: Sized;
+
+    /// Returns when the earliest delayed element becomes due.
+    fn next_due(&self) -> Option<Instant>;
+
+    /// Returns a copy of the element with the given identifier, searching the whole queue.
+    fn retrieve_by_id_linear(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone;
+
+    /// Returns a copy of the element with the given identifier.
+    fn retrieve_by_id(&self, id: QueueId) -> Result<T, QueueError<T, P>>
+    where
+        T: Clone;
+
+    /// Pops the next item in the queue.
+    fn pop(&mut self) -> Option<T>;
+
+    /// Hands out the next item in the queue without removing it.
+    fn reserve(&mut self, visibility_timeout: Duration) -> Option<(T, Lease)>
+    where
+        T: Clone;
+
+    /// Settles a lease by removing its element from the queue for good.
+    fn ack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>>;
+
+    /// Settles a lease by reporting that processing failed.
+    fn nack(&mut self, lease: Lease) -> Result<(), QueueError<T, P>>;
+
+    /// Returns how many times processing an element has been reported as failed.
+    fn failures(&self, id: QueueId) -> u32;
+
+    /// Attaches a dead-letter queue, or changes its limit.
+    fn set_dead_letter(&mut self, max_failures: u32);
+
+    /// Returns the dead-letter queue, if one is attached.
+    fn dead_letters(&self) -> Option<&dyn PriorityQueue<T, P>>;
+
+    /// Moves an element out of the dead-letter queue and back into this one.
+    fn requeue_dead_letter(&mut self, id: QueueId) -> Result<(), QueueError<T, P>>;
+
+    /// Removes an element from the dead-letter queue for good.
+    fn drop_dead_letter(&mut self, id: QueueId) -> Option<T>;
+
+    /// Pops an element based on the id.
+    fn pop_by_id(&mut self, id: QueueId) -> Option<T>;
+
+    /// Removes every expired element from the queue.
+    fn purge_expired(&mut self) -> Vec<(QueueId, T)>;
+
+    /// Changes the priority of an element, keeping its identifier.
+    fn reprioritize(&mut self, id: QueueId, priority: P, position: FifoPosition) -> Result<(), QueueError<T, P>>;
+
+    /// Locks an element in place.
+    fn lock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>>;
+
+    /// Unlocks an element.
+    fn unlock(&mut self, id: QueueId) -> Result<(), QueueError<T, P>>;
+
+    /// Peeks the next item in the queue, along with its identifier.
+    fn peek(&self) -> Option<(&T, QueueId)>;
+
+    /// Returns all elements in the queue, in the order they would be popped.
+    fn get_elements(&self) -> Vec<T>
+    where
+        T: Clone;
+
+    /// Returns a copy of the entire queue, including delayed and expired elements.
+    fn all(&self) -> BTreeMap<QueueFlag<P>, T>
+    where
+        T: Clone;
+
+    /// Returns the size of the queue, including delayed elements.
+    fn size(&self) -> usize;
+
+    /// Returns whether the queue holds no elements.
+    fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+}
+
+/// The extra API of queues with a maximum size, such as `SizedQueue`.
+pub trait BoundedQueue<T, P = u32>: PriorityQueue<T, P>
+where
+    T: Queueable,
+    P: Ord + Clone,
+{
+    /// Returns the maximum size of the queue.
+    fn max_size(&self) -> u32;
+
+    /// Increases the maximum size of the queue.
+    fn expand(&mut self, size: usize) -> Result<(), QueueError<T, P>>;
+
+    /// Sets the maximum size of the queue, growing or shrinking it.
+    fn resize(&mut self, max_size: u32, policy: ShrinkPolicy) -> Result<Vec<Evicted<T>>, QueueError<T, P>>;
+
+    /// Returns what `push_evicting` does when the queue is full.
+    fn overflow_policy(&self) -> OverflowPolicy;
+
+    /// Sets what `push_evicting` does when the queue is full.
+    fn set_overflow_policy(&mut self, policy: OverflowPolicy);
+
+    /// Pushes a prioritized item, making room for it under the overflow policy if the queue is full.
+    fn push_evicting(&mut self, item: Prio<T, P>) -> Result<EvictingPush<T>, QueueError<T, P>>;
+
+    /// Returns a percentage of the queue's size.
+    fn percentage_full(&self) -> f32 {
+        self.size() as f32 * 100.0 / self.max_size() as f32
+    }
+
+    /// Returns whether the queue is at or over its maximum size.
+    fn is_full(&self) -> bool {
+        self.size() >= self.max_size() as usize
+    }
+}